@@ -0,0 +1,125 @@
+#ifndef TETRIS_H
+#define TETRIS_H
+
+/* Generated by cbindgen from src/capi.rs, don't edit it by hand. */
+
+#include <stdint.h>
+
+#define TETRIS_INPUT_LEFT 1
+
+#define TETRIS_INPUT_RIGHT (1 << 1)
+
+#define TETRIS_INPUT_ROTATE (1 << 2)
+
+#define TETRIS_INPUT_SOFT_DROP (1 << 3)
+
+#define TETRIS_INPUT_HARD_DROP (1 << 4)
+
+#define TETRIS_INPUT_HOLD (1 << 5)
+
+#define TETRIS_RUNNING 0
+
+#define TETRIS_WON 1
+
+#define TETRIS_LOST 2
+
+#define TETRIS_NOT_OVER 0
+
+#define TETRIS_BLOCK_OUT 1
+
+#define TETRIS_LOCK_OUT 2
+
+#define TETRIS_GARBAGE_TOP_OUT 3
+
+/*
+ * A game, opaque to C.
+ */
+typedef struct TetrisEngine TetrisEngine;
+
+/*
+ * Creates an engine playing `mode` ("endless", "sprint", "ultra", "marathon",
+ * "dig", "finesse" or "classic", endless if null). The same seed always gives
+ * the same pieces. Returns null if the mode is unknown.
+ *
+ * # Safety
+ *
+ * `mode` has to be null or a nul-terminated string.
+ */
+TetrisEngine *tetris_engine_new(uint64_t seed, const char *mode);
+
+/*
+ * # Safety
+ *
+ * `engine` has to come from `tetris_engine_new` and can't be used afterwards.
+ * Null is ignored.
+ */
+void tetris_engine_free(TetrisEngine *engine);
+
+/*
+ * Advances the game by one tick with `inputs`. Returns `TETRIS_RUNNING`, or
+ * `TETRIS_WON`/`TETRIS_LOST` once the game is over, after which it doesn't
+ * change anymore.
+ *
+ * # Safety
+ *
+ * `engine` has to be a valid engine.
+ */
+int32_t tetris_engine_step(TetrisEngine *engine, uint32_t inputs);
+
+/*
+ * Why the game got lost: `TETRIS_BLOCK_OUT`, `TETRIS_LOCK_OUT` or
+ * `TETRIS_GARBAGE_TOP_OUT`, `TETRIS_NOT_OVER` while it isn't.
+ *
+ * # Safety
+ *
+ * `engine` has to be a valid engine.
+ */
+int32_t tetris_engine_game_over(const TetrisEngine *engine);
+
+/*
+ * # Safety
+ *
+ * `engine` has to be a valid engine.
+ */
+uint32_t tetris_engine_width(const TetrisEngine *engine);
+
+/*
+ * # Safety
+ *
+ * `engine` has to be a valid engine.
+ */
+uint32_t tetris_engine_height(const TetrisEngine *engine);
+
+/*
+ * The cell at (`x`, `y`), `y` going down from the top of the visible lines,
+ * with the falling piece: 0 if it's empty, the id of the piece it comes from
+ * plus one, or 255 for garbage. Cells out of the map are empty.
+ *
+ * # Safety
+ *
+ * `engine` has to be a valid engine.
+ */
+uint8_t tetris_engine_cell(const TetrisEngine *engine, uint32_t x, uint32_t y);
+
+/*
+ * # Safety
+ *
+ * `engine` has to be a valid engine.
+ */
+uint32_t tetris_engine_score(const TetrisEngine *engine);
+
+/*
+ * # Safety
+ *
+ * `engine` has to be a valid engine.
+ */
+uint32_t tetris_engine_lines(const TetrisEngine *engine);
+
+/*
+ * # Safety
+ *
+ * `engine` has to be a valid engine.
+ */
+uint32_t tetris_engine_level(const TetrisEngine *engine);
+
+#endif /* TETRIS_H */
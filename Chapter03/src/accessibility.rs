@@ -0,0 +1,84 @@
+// Options helping to tell the pieces apart, set in "settings.txt": colour
+// schemes which colour-blind players can read, the letter of their piece drawn
+// on the cells, and a high-contrast look. Slowing the game down is done with
+// the speed of its `Timing`.
+
+use garbage::GARBAGE;
+use pieces::PieceSet;
+
+pub type Rgb = (u8, u8, u8);
+
+/// Colours of the high-contrast mode.
+pub const HIGH_CONTRAST_BACKGROUND: Rgb = (0, 0, 0);
+pub const HIGH_CONTRAST_FOREGROUND: Rgb = (255, 255, 255);
+pub const HIGH_CONTRAST_GARBAGE: Rgb = (170, 170, 170);
+
+/// Okabe and Ito's colours, distinct with protanopia and deuteranopia.
+const RED_GREEN: [Rgb; 7] = [(86, 180, 233), (0, 114, 178), (230, 159, 0), (240, 228, 66),
+                             (0, 158, 115), (213, 94, 0), (204, 121, 167)];
+/// Paul Tol's "vibrant" colours, also distinct with tritanopia.
+const BLUE_YELLOW: [Rgb; 7] = [(51, 187, 238), (0, 119, 187), (238, 119, 51), (187, 187, 187),
+                               (0, 153, 136), (204, 51, 17), (238, 51, 119)];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorScheme {
+    /// The colours of the piece set, or the ones of the theme.
+    Standard,
+    RedGreen,
+    BlueYellow,
+}
+
+impl ColorScheme {
+    pub const ALL: [ColorScheme; 3] = [ColorScheme::Standard, ColorScheme::RedGreen,
+                                       ColorScheme::BlueYellow];
+
+    pub fn from_name(name: &str) -> Option<ColorScheme> {
+        ColorScheme::ALL.iter().cloned().find(|scheme| scheme.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorScheme::Standard => "standard",
+            ColorScheme::RedGreen => "red_green",
+            ColorScheme::BlueYellow => "blue_yellow",
+        }
+    }
+
+    /// Colour of the piece of id `id`, or `None` to keep the usual one. The
+    /// colours are reused by sets of more than seven pieces.
+    pub fn piece_color(self, id: u8) -> Option<Rgb> {
+        let colors = match self {
+            ColorScheme::Standard => return None,
+            ColorScheme::RedGreen => &RED_GREEN,
+            ColorScheme::BlueYellow => &BLUE_YELLOW,
+        };
+        Some(colors[id as usize % colors.len()])
+    }
+
+    /// Colour of a cell of the game map.
+    pub fn color(self, piece_set: &PieceSet, cell: u8) -> Rgb {
+        match cell {
+            GARBAGE => piece_set.color(cell),
+            _ => self.piece_color(cell - 1).unwrap_or_else(|| piece_set.color(cell)),
+        }
+    }
+}
+
+/// Letter drawn on the cells with the `patterns` setting: the first one of the
+/// piece's name, or '#' for garbage.
+pub fn cell_letter(piece_set: &PieceSet, cell: u8) -> char {
+    match cell {
+        GARBAGE => '#',
+        _ => piece_set.pieces.get(cell as usize - 1)
+                             .and_then(|piece| piece.name.chars().next())
+                             .map(|c| c.to_ascii_uppercase())
+                             .unwrap_or('#'),
+    }
+}
+
+/// Black or white, whichever is easier to read over `background`.
+pub fn text_color(background: Rgb) -> Rgb {
+    let (r, g, b) = background;
+    let luma = 299 * r as u32 + 587 * g as u32 + 114 * b as u32;
+    if luma > 140_000 { (0, 0, 0) } else { (255, 255, 255) }
+}
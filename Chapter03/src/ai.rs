@@ -0,0 +1,168 @@
+use std::fmt;
+use std::io;
+
+use {read_from_file, write_into_file};
+use board::{Board, Row, FULL_ROW};
+use tetrimino::Tetrimino;
+use tetris::Tetris;
+
+pub const NB_FEATURES: usize = 4;
+pub const FEATURE_NAMES: [&str; NB_FEATURES] =
+    ["aggregate_height", "complete_lines", "holes", "bumpiness"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Weights {
+    pub values: [f64; NB_FEATURES],
+}
+
+impl Weights {
+    pub fn new(values: [f64; NB_FEATURES]) -> Weights {
+        Weights { values }
+    }
+
+    /// Weights files contain the values separated by spaces, in the same order
+    /// as `FEATURE_NAMES`.
+    pub fn load(file_name: &str) -> io::Result<Weights> {
+        let content = read_from_file(file_name)?;
+        let parsed = content.split_whitespace()
+                            .map(|value| value.parse::<f64>())
+                            .collect::<Result<Vec<_>, _>>()
+                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if parsed.len() != NB_FEATURES {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("expected {} weights, found {}",
+                                              NB_FEATURES, parsed.len())));
+        }
+        let mut values = [0.; NB_FEATURES];
+        values.copy_from_slice(&parsed);
+        Ok(Weights::new(values))
+    }
+
+    pub fn save(&self, file_name: &str) -> io::Result<()> {
+        write_into_file(&format!("{}\n", self), file_name)
+    }
+
+    /// Only the direction of the weights vector matters when comparing
+    /// placements, so we keep it at length 1.
+    pub fn normalize(&mut self) {
+        let norm = self.values.iter().map(|v| v * v).sum::<f64>().sqrt();
+        if norm > 0. {
+            for value in self.values.iter_mut() {
+                *value /= norm;
+            }
+        }
+    }
+}
+
+impl Default for Weights {
+    fn default() -> Weights {
+        Weights::new([-0.510066, 0.760666, -0.35663, -0.184483])
+    }
+}
+
+impl fmt::Display for Weights {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let values = self.values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        write!(f, "{}", values.join(" "))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Placement {
+    pub state: u8,
+    pub x: isize,
+    pub score: f64,
+}
+
+/// Tries every rotation and column for `piece`, dropping it straight down from
+/// its current height, and returns the one the weights like best.
+pub fn best_placement(game_map: &Board, piece: &Tetrimino,
+                      weights: &Weights) -> Option<Placement> {
+    let mut best: Option<Placement> = None;
+    let width = game_map.width() as isize;
+
+    for state in 0..piece.states.len() {
+        for x in -(piece.width() as isize)..width {
+            if !piece.test_position(game_map, state, x, piece.y) {
+                continue
+            }
+            let mut y = piece.y;
+            while piece.test_position(game_map, state, x, y + 1) {
+                y += 1;
+            }
+            // Only the bits of the rows matter to the evaluation.
+            let mut rows = game_map.rows().to_vec();
+            lock_piece(&mut rows, &piece.masks[state], x, y);
+            let lines = clear_lines(&mut rows);
+            let score = evaluate(&rows, game_map.width(), lines, weights);
+            if best.as_ref().map(|b| score > b.score).unwrap_or(true) {
+                best = Some(Placement {
+                    state: state as u8,
+                    x,
+                    score,
+                });
+            }
+        }
+    }
+    best
+}
+
+/// Moves the current piece of `tetris` above its best placement. The piece
+/// still has to be dropped.
+pub fn move_piece(tetris: &mut Tetris, weights: &Weights) -> bool {
+    if let Some(ref mut piece) = tetris.current_piece {
+        if let Some(placement) = best_placement(&tetris.game_map, piece, weights) {
+            piece.current_state = placement.state;
+            piece.x = placement.x;
+            return true;
+        }
+    }
+    false
+}
+
+pub fn evaluate(rows: &[Row], width: usize, complete_lines: u32, weights: &Weights) -> f64 {
+    let features = features(rows, width, complete_lines);
+    features.iter().zip(weights.values.iter()).map(|(f, w)| f * w).sum()
+}
+
+pub fn features(rows: &[Row], width: usize, complete_lines: u32) -> [f64; NB_FEATURES] {
+    let mut heights = vec![0; width];
+    let mut holes = 0;
+    // Columns which have a block somewhere above the current row.
+    let mut covered: Row = 0;
+
+    for (y, &row) in rows.iter().enumerate() {
+        holes += (covered & !row).count_ones();
+        let tops = row & !covered;
+        for (x, height) in heights.iter_mut().enumerate() {
+            if tops & 1 << x != 0 {
+                *height = rows.len() - y;
+            }
+        }
+        covered |= row;
+    }
+    let aggregate_height = heights.iter().sum::<usize>();
+    let bumpiness = heights.windows(2)
+                           .map(|w| (w[0] as isize - w[1] as isize).unsigned_abs())
+                           .sum::<usize>();
+    [aggregate_height as f64, complete_lines as f64, holes as f64, bumpiness as f64]
+}
+
+/// The position has to be valid.
+fn lock_piece(rows: &mut [Row], mask: &[Row], x: isize, y: usize) {
+    for (shift_y, &bits) in mask.iter().enumerate() {
+        if bits != 0 {
+            rows[y + shift_y] |= if x >= 0 { bits << x } else { bits >> -x };
+        }
+    }
+}
+
+fn clear_lines(rows: &mut Vec<Row>) -> u32 {
+    let height = rows.len();
+    rows.retain(|&row| row != FULL_ROW);
+    let removed = height - rows.len();
+    for _ in 0..removed {
+        rows.insert(0, 0);
+    }
+    removed as u32
+}
@@ -0,0 +1,196 @@
+// Visual effects, shared by the frontends. They only ever look at the game: the
+// time lines take to vanish is the engine's line-clear delay, so turning the
+// effects off doesn't change how the game plays.
+
+use std::time::Duration;
+
+use randomizer::XorShift;
+use tetris::Event;
+
+const SHAKE_TIME: f64 = 0.15;
+/// Largest shake offset, in cells.
+const SHAKE_AMPLITUDE: f64 = 0.15;
+const PARTICLES_PER_CELL: usize = 3;
+const PARTICLE_LIFE: f64 = 0.8;
+/// In cells per second squared.
+const GRAVITY: f64 = 30.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClearStyle {
+    /// The lines blink white.
+    Flash,
+    /// The cells of the lines disappear one after the other.
+    Dissolve,
+    /// The cells of the lines burst into particles.
+    Particles,
+}
+
+impl ClearStyle {
+    pub fn from_name(name: &str) -> Option<ClearStyle> {
+        match name {
+            "flash" => Some(ClearStyle::Flash),
+            "dissolve" => Some(ClearStyle::Dissolve),
+            "particles" => Some(ClearStyle::Particles),
+            _ => None,
+        }
+    }
+}
+
+/// How a cell of a line being cleared has to be drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CellEffect {
+    Normal,
+    Hidden,
+    /// Mixed with white, from 0 (not at all) to 1.
+    Flash(f64),
+}
+
+pub struct Particle {
+    /// Position in cells, from the top-left corner of the map.
+    pub x: f64,
+    pub y: f64,
+    speed_x: f64,
+    speed_y: f64,
+    pub color: u8,
+    /// Seconds left to live.
+    life: f64,
+}
+
+impl Particle {
+    /// From 1 when created down to 0.
+    pub fn fade(&self) -> f64 {
+        (self.life / PARTICLE_LIFE).max(0.)
+    }
+}
+
+struct LineClear {
+    rows: Vec<usize>,
+    board: Vec<Vec<u8>>,
+}
+
+pub struct Animations {
+    enabled: bool,
+    style: ClearStyle,
+    screen_shake: bool,
+    clear: Option<LineClear>,
+    // How far the line clear is, given by the game.
+    clear_progress: f64,
+    particles: Vec<Particle>,
+    shake_left: f64,
+    rng: XorShift,
+}
+
+impl Animations {
+    pub fn new(enabled: bool, style: ClearStyle, screen_shake: bool) -> Animations {
+        Animations {
+            enabled,
+            style,
+            screen_shake,
+            clear: None,
+            clear_progress: 0.,
+            particles: Vec::new(),
+            shake_left: 0.,
+            rng: XorShift::new(0),
+        }
+    }
+
+    pub fn handle(&mut self, event: &Event) {
+        if !self.enabled {
+            return
+        }
+        match *event {
+            Event::Locked { ref rows, ref board } if !rows.is_empty() => {
+                if self.style == ClearStyle::Particles {
+                    for &y in rows {
+                        for (x, &cell) in board[y].iter().enumerate() {
+                            for _ in 0..PARTICLES_PER_CELL {
+                                self.particles.push(Particle {
+                                    x: x as f64 + self.rng.next_f64(),
+                                    y: y as f64 + self.rng.next_f64(),
+                                    speed_x: (self.rng.next_f64() - 0.5) * 8.,
+                                    speed_y: -self.rng.next_f64() * 10.,
+                                    color: cell,
+                                    life: PARTICLE_LIFE,
+                                });
+                            }
+                        }
+                    }
+                }
+                self.clear = Some(LineClear {
+                    rows: rows.clone(),
+                    board: board.clone(),
+                });
+                self.clear_progress = 0.;
+            }
+            Event::HardDrop if self.screen_shake => self.shake_left = SHAKE_TIME,
+            _ => {}
+        }
+    }
+
+    /// `elapsed` is the time since the last frame and `clear_progress` how far
+    /// the game is in its line-clear delay, if it is in it.
+    pub fn update(&mut self, elapsed: Duration, clear_progress: Option<f64>) {
+        let elapsed = elapsed.as_secs_f64();
+        match clear_progress {
+            Some(progress) => self.clear_progress = progress,
+            None => self.clear = None,
+        }
+        for particle in &mut self.particles {
+            particle.x += particle.speed_x * elapsed;
+            particle.y += particle.speed_y * elapsed;
+            particle.speed_y += GRAVITY * elapsed;
+            particle.life -= elapsed;
+        }
+        self.particles.retain(|particle| particle.life > 0.);
+        self.shake_left = (self.shake_left - elapsed).max(0.);
+    }
+
+    /// The map to draw instead of the game's while lines are being cleared.
+    pub fn board(&self) -> Option<&[Vec<u8>]> {
+        self.clear.as_ref().map(|clear| clear.board.as_slice())
+    }
+
+    pub fn cell_effect(&self, x: usize, y: usize) -> CellEffect {
+        let clear = match self.clear {
+            Some(ref clear) if clear.rows.contains(&y) => clear,
+            _ => return CellEffect::Normal,
+        };
+        match self.style {
+            // Three blinks, the last one fading out.
+            ClearStyle::Flash => {
+                let blink = (self.clear_progress * 6.) as u32 & 1 == 0;
+                if self.clear_progress > 0.8 {
+                    CellEffect::Hidden
+                } else if blink {
+                    CellEffect::Flash(1. - self.clear_progress)
+                } else {
+                    CellEffect::Normal
+                }
+            }
+            // Every cell gets a fixed pseudo-random time to go.
+            ClearStyle::Dissolve => {
+                let hash = (x * 7 + y * 13 + clear.rows.len() * 3) % 10;
+                if (hash as f64) < self.clear_progress * 10. {
+                    CellEffect::Hidden
+                } else {
+                    CellEffect::Normal
+                }
+            }
+            ClearStyle::Particles => CellEffect::Hidden,
+        }
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    /// Offset of the whole map, in cells.
+    pub fn shake(&self) -> (f64, f64) {
+        if self.shake_left <= 0. {
+            return (0., 0.);
+        }
+        let strength = self.shake_left / SHAKE_TIME * SHAKE_AMPLITUDE;
+        let phase = self.shake_left * 80.;
+        (phase.sin() * strength, phase.cos() * strength)
+    }
+}
@@ -0,0 +1,153 @@
+// The game map is kept twice: one bit per cell in a `Row` for every line, which
+// makes collisions a few ANDs and full lines a comparison, and the value of every
+// cell (the piece it came from) for drawing. Both are only changed through
+// `Board` so that they stay in sync.
+//
+// Above the lines the player sees, the map has a few hidden ones, the "vanish
+// zone", where pieces spawn and where blocks can be pushed up by garbage.
+
+pub const WIDTH: usize = 10;
+/// Lines shown to the player.
+pub const VISIBLE_HEIGHT: usize = 16;
+/// Hidden lines above the visible ones.
+pub const VANISH_ZONE: usize = 4;
+pub const HEIGHT: usize = VANISH_ZONE + VISIBLE_HEIGHT;
+
+/// The cells of a line, column `x` being bit `x`.
+pub type Row = u16;
+
+pub const FULL_ROW: Row = (1 << WIDTH) - 1;
+
+/// Bits of the non-empty cells of `line`.
+pub fn row_bits(line: &[u8]) -> Row {
+    line.iter().enumerate().filter(|&(_, &cell)| cell != 0).fold(0, |bits, (x, _)| bits | 1 << x)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Board {
+    rows: Vec<Row>,
+    lines: Vec<Vec<u8>>,
+}
+
+impl Board {
+    pub fn empty() -> Board {
+        Board {
+            rows: vec![0; HEIGHT],
+            lines: vec![vec![0; WIDTH]; HEIGHT],
+        }
+    }
+
+    /// `lines` have to be `WIDTH` cells wide.
+    pub fn from_lines(lines: Vec<Vec<u8>>) -> Board {
+        Board {
+            rows: lines.iter().map(|line| row_bits(line)).collect(),
+            lines,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        WIDTH
+    }
+
+    /// Height of the whole map, vanish zone included.
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn visible_height(&self) -> usize {
+        self.rows.len() - VANISH_ZONE
+    }
+
+    /// The value of every cell, line after line from the top.
+    pub fn lines(&self) -> &[Vec<u8>] {
+        &self.lines
+    }
+
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|&row| row == 0)
+    }
+
+    /// Whether the cell is taken. Everything outside of the map is.
+    pub fn is_occupied(&self, x: isize, y: isize) -> bool {
+        x < 0 || y < 0 || x as usize >= WIDTH ||
+        self.rows.get(y as usize).map(|&row| row & 1 << x != 0).unwrap_or(true)
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, cell: u8) {
+        self.lines[y][x] = cell;
+        if cell == 0 {
+            self.rows[y] &= !(1 << x);
+        } else {
+            self.rows[y] |= 1 << x;
+        }
+    }
+
+    /// Whether a piece state, as given by its mask, fits with its top-left
+    /// corner at (`x`, `y`).
+    pub fn fits(&self, mask: &[Row], x: isize, y: usize) -> bool {
+        mask.iter().enumerate().all(|(shift_y, &bits)| {
+            if bits == 0 {
+                return true
+            }
+            let row = match self.rows.get(y + shift_y) {
+                Some(&row) => row,
+                None => return false,
+            };
+            // Bits shifted out of the row are cells out of the map.
+            let shifted = if x >= 0 {
+                if (bits.leading_zeros() as isize) < x {
+                    return false
+                }
+                bits << x
+            } else {
+                if (bits.trailing_zeros() as isize) < -x {
+                    return false
+                }
+                bits >> -x
+            };
+            shifted & !FULL_ROW == 0 && shifted & row == 0
+        })
+    }
+
+    /// Copies the cells of a piece state into the map. Cells out of the map are
+    /// ignored.
+    pub fn lock(&mut self, state: &[Vec<u8>], x: isize, y: usize) {
+        for (shift_y, line) in state.iter().enumerate() {
+            for (shift_x, &cell) in line.iter().enumerate() {
+                let cell_x = x + shift_x as isize;
+                if cell != 0 && y + shift_y < self.height() && cell_x >= 0 && (cell_x as usize) < WIDTH {
+                    self.set(cell_x as usize, y + shift_y, cell);
+                }
+            }
+        }
+    }
+
+    /// Removes the full lines, the lines above them going down. Returns the
+    /// removed lines, as they were numbered before.
+    pub fn clear_full_lines(&mut self) -> Vec<usize> {
+        let full = (0..self.rows.len()).filter(|&y| self.rows[y] == FULL_ROW).collect::<Vec<_>>();
+        for &y in full.iter().rev() {
+            self.rows.remove(y);
+            self.lines.remove(y);
+        }
+        for _ in 0..full.len() {
+            self.rows.insert(0, 0);
+            self.lines.insert(0, vec![0; WIDTH]);
+        }
+        full
+    }
+
+    /// Pushes `line` from the bottom of the map. Returns `false` if blocks got
+    /// pushed out of the top of the vanish zone.
+    pub fn push_line(&mut self, line: Vec<u8>) -> bool {
+        let top = self.rows.remove(0);
+        self.lines.remove(0);
+        self.rows.push(row_bits(&line));
+        self.lines.push(line);
+        top == 0
+    }
+}
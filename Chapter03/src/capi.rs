@@ -0,0 +1,177 @@
+// C interface of the engine, built with the `capi` feature. The functions are
+// declared in "include/tetris.h", which cbindgen generates from this file (see
+// "cbindgen.toml"): it has to be regenerated when they change.
+//
+// An engine runs a single game with the standard pieces. It's advanced one tick
+// (1/60th of a second) at a time with the inputs of the player for that tick,
+// given as a combination of the `TETRIS_INPUT_*` flags.
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+
+use {Action, Game, GameMode, GameOver, Outcome, PieceSet, Tetris};
+
+pub const TETRIS_INPUT_LEFT: u32 = 1;
+pub const TETRIS_INPUT_RIGHT: u32 = 1 << 1;
+pub const TETRIS_INPUT_ROTATE: u32 = 1 << 2;
+pub const TETRIS_INPUT_SOFT_DROP: u32 = 1 << 3;
+pub const TETRIS_INPUT_HARD_DROP: u32 = 1 << 4;
+pub const TETRIS_INPUT_HOLD: u32 = 1 << 5;
+
+pub const TETRIS_RUNNING: i32 = 0;
+pub const TETRIS_WON: i32 = 1;
+pub const TETRIS_LOST: i32 = 2;
+
+pub const TETRIS_NOT_OVER: i32 = 0;
+pub const TETRIS_BLOCK_OUT: i32 = 1;
+pub const TETRIS_LOCK_OUT: i32 = 2;
+pub const TETRIS_GARBAGE_TOP_OUT: i32 = 3;
+
+/// Inputs, in the order they're applied when given together.
+const INPUTS: [(u32, Action); 6] = [(TETRIS_INPUT_HOLD, Action::Hold),
+                                    (TETRIS_INPUT_LEFT, Action::MoveLeft),
+                                    (TETRIS_INPUT_RIGHT, Action::MoveRight),
+                                    (TETRIS_INPUT_ROTATE, Action::Rotate),
+                                    (TETRIS_INPUT_SOFT_DROP, Action::SoftDrop),
+                                    (TETRIS_INPUT_HARD_DROP, Action::HardDrop)];
+
+/// A game, opaque to C.
+pub struct TetrisEngine {
+    game: Game,
+    outcome: Option<Outcome>,
+}
+
+fn status(outcome: Option<Outcome>) -> i32 {
+    match outcome {
+        None => TETRIS_RUNNING,
+        Some(Outcome::Won) => TETRIS_WON,
+        Some(Outcome::Lost(_)) | Some(Outcome::Quit) => TETRIS_LOST,
+    }
+}
+
+/// Creates an engine playing `mode` ("endless", "sprint", "ultra", "marathon",
+/// "dig", "finesse" or "classic", endless if null). The same seed always gives
+/// the same pieces. Returns null if the mode is unknown.
+///
+/// # Safety
+///
+/// `mode` has to be null or a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_engine_new(seed: u64, mode: *const c_char) -> *mut TetrisEngine {
+    let mode = if mode.is_null() {
+        GameMode::Endless
+    } else {
+        match CStr::from_ptr(mode).to_str().ok().and_then(GameMode::from_name) {
+            Some(mode) => mode,
+            None => return ptr::null_mut(),
+        }
+    };
+    let engine = TetrisEngine {
+        game: Game::new(Tetris::with_seed(seed, mode, PieceSet::standard())),
+        outcome: None,
+    };
+    Box::into_raw(Box::new(engine))
+}
+
+/// # Safety
+///
+/// `engine` has to come from `tetris_engine_new` and can't be used afterwards.
+/// Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_engine_free(engine: *mut TetrisEngine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+/// Advances the game by one tick with `inputs`. Returns `TETRIS_RUNNING`, or
+/// `TETRIS_WON`/`TETRIS_LOST` once the game is over, after which it doesn't
+/// change anymore.
+///
+/// # Safety
+///
+/// `engine` has to be a valid engine.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_engine_step(engine: *mut TetrisEngine, inputs: u32) -> i32 {
+    let engine = &mut *engine;
+    if engine.outcome.is_none() {
+        let actions = INPUTS.iter().filter(|&&(flag, _)| inputs & flag != 0)
+                                   .map(|&(_, action)| action)
+                                   .collect::<Vec<_>>();
+        engine.outcome = engine.game.tick(&actions, None);
+        // Nothing reads them, they would pile up.
+        engine.game.take_events();
+    }
+    status(engine.outcome)
+}
+
+/// Why the game got lost: `TETRIS_BLOCK_OUT`, `TETRIS_LOCK_OUT` or
+/// `TETRIS_GARBAGE_TOP_OUT`, `TETRIS_NOT_OVER` while it isn't.
+///
+/// # Safety
+///
+/// `engine` has to be a valid engine.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_engine_game_over(engine: *const TetrisEngine) -> i32 {
+    match (*engine).outcome {
+        Some(Outcome::Lost(GameOver::BlockOut)) => TETRIS_BLOCK_OUT,
+        Some(Outcome::Lost(GameOver::LockOut)) => TETRIS_LOCK_OUT,
+        Some(Outcome::Lost(GameOver::GarbageTopOut)) => TETRIS_GARBAGE_TOP_OUT,
+        // Engines don't play puzzles, which are the only games running out of
+        // pieces.
+        _ => TETRIS_NOT_OVER,
+    }
+}
+
+/// # Safety
+///
+/// `engine` has to be a valid engine.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_engine_width(engine: *const TetrisEngine) -> u32 {
+    (*engine).game.tetris.game_map.width() as u32
+}
+
+/// # Safety
+///
+/// `engine` has to be a valid engine.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_engine_height(engine: *const TetrisEngine) -> u32 {
+    (*engine).game.tetris.game_map.visible_height() as u32
+}
+
+/// The cell at (`x`, `y`), `y` going down from the top of the visible lines,
+/// with the falling piece: 0 if it's empty, the id of the piece it comes from
+/// plus one, or 255 for garbage. Cells out of the map are empty.
+///
+/// # Safety
+///
+/// `engine` has to be a valid engine.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_engine_cell(engine: *const TetrisEngine, x: u32, y: u32) -> u8 {
+    (*engine).game.tetris.visible_cell(x as usize, y as usize)
+}
+
+/// # Safety
+///
+/// `engine` has to be a valid engine.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_engine_score(engine: *const TetrisEngine) -> u32 {
+    (*engine).game.tetris.score
+}
+
+/// # Safety
+///
+/// `engine` has to be a valid engine.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_engine_lines(engine: *const TetrisEngine) -> u32 {
+    (*engine).game.tetris.nb_lines
+}
+
+/// # Safety
+///
+/// `engine` has to be a valid engine.
+#[no_mangle]
+pub unsafe extern "C" fn tetris_engine_level(engine: *const TetrisEngine) -> u32 {
+    (*engine).game.tetris.current_level
+}
@@ -0,0 +1,54 @@
+// The rules of the NES version of the game, played in the classic mode. Its
+// pieces are the ones of "pieces/classic.txt", drawn with `Randomizer::nes`,
+// and they can't be held or hard dropped.
+
+use std::time::Duration;
+
+/// Highest level a game can start at, as on the NES by holding A.
+pub const MAX_START_LEVEL: u32 = 19;
+
+/// Frames a piece takes to fall one line at each level. From level 29 on, it's
+/// a single frame.
+const GRAVITY: [u32; 29] = [48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3,
+                            2, 2, 2, 2, 2, 2, 2, 2, 2, 2];
+/// Points for clearing one to four lines at once, times the level plus one.
+const LINE_SCORES: [u32; 4] = [40, 100, 300, 1200];
+/// Frames per second of the NES.
+const FRAME_RATE: f64 = 60.0988;
+
+fn frames(nb: u32) -> Duration {
+    Duration::from_secs_f64(nb as f64 / FRAME_RATE)
+}
+
+/// Time (in milliseconds) a piece takes to fall one line at `level`. Rounded
+/// down, it takes as many ticks of the game as frames on the NES.
+pub fn fall_delay(level: u32) -> u32 {
+    let nb = GRAVITY.get(level as usize).cloned().unwrap_or(1);
+    frames(nb).as_millis() as u32
+}
+
+pub fn line_score(nb_lines: usize, level: u32) -> u32 {
+    match nb_lines {
+        0 => 0,
+        nb => LINE_SCORES[nb.min(LINE_SCORES.len()) - 1] * (level + 1),
+    }
+}
+
+/// Level reached after clearing `nb_lines` lines from level `start`. The first
+/// level up comes after `start * 10 + 10` lines, or after `start * 10 - 50`
+/// lines if that's fewer but still at least 100, then every 10 lines.
+pub fn level(start: u32, nb_lines: u32) -> u32 {
+    let first = (start * 10 + 10).min((start * 10).saturating_sub(50).max(100));
+    if nb_lines < first {
+        start
+    } else {
+        start + 1 + (nb_lines - first) / 10
+    }
+}
+
+/// Delay before the next piece when the last one locked with its lowest cell
+/// on line `height`, 0 being the bottom line: 10 frames on the two bottom
+/// lines, and 2 more for every 4 lines above, up to 18.
+pub fn entry_delay(height: usize) -> Duration {
+    frames((10 + (height as u32 + 2) / 4 * 2).min(18))
+}
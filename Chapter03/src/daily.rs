@@ -0,0 +1,170 @@
+// The daily challenge: the seed of the randomizer comes from the date (in UTC),
+// so that everyone gets the same pieces on the same day. Every profile gets a
+// single ranked attempt per day. The attempts are kept in "scores_daily.txt",
+// one "DATE PROFILE SCORE" line each, so that the leaderboards of the previous
+// days stay available.
+
+use std::cmp::Reverse;
+use std::fmt;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use {read_from_file, write_into_file};
+
+pub const DAILY_FILE: &str = "scores_daily.txt";
+/// Longest name of a profile.
+pub const MAX_PROFILE_LEN: usize = 20;
+/// Previous days shown under the leaderboard of the day.
+const NB_HISTORY_DAYS: usize = 7;
+
+/// A day of the Gregorian calendar.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: u32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    /// The current day in UTC, the same for everyone at the same time.
+    pub fn today() -> Date {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        Date::from_days((secs / 86_400) as i64)
+    }
+
+    /// The day `days` days after the 1st of January 1970, with Howard
+    /// Hinnant's `civil_from_days`.
+    fn from_days(days: i64) -> Date {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 -
+                           day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        // Months counted from March, so that February comes last.
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+        Date { year: year as u32, month: month as u32, day: day as u32 }
+    }
+
+    /// Parses a "YYYY-MM-DD" date.
+    pub fn parse(s: &str) -> Option<Date> {
+        let values = s.split('-').map(|value| value.parse::<u32>().ok()).collect::<Option<Vec<_>>>()?;
+        match values[..] {
+            [year, month, day] if (1..=12).contains(&month) && day >= 1 &&
+                                  day <= days_in_month(year, month) => Some(Date { year, month, day }),
+            _ => None,
+        }
+    }
+
+    /// The date as the number YYYYMMDD.
+    pub fn seed(self) -> u64 {
+        self.year as u64 * 10_000 + self.month as u64 * 100 + self.day as u64
+    }
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Profiles are stored in lines of words, so their names can't have spaces.
+pub fn is_valid_profile(name: &str) -> bool {
+    !name.is_empty() && name.len() <= MAX_PROFILE_LEN &&
+    name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub date: Date,
+    pub profile: String,
+    pub score: u32,
+}
+
+/// Reads the attempts of every day. Invalid lines are skipped.
+pub fn load() -> Vec<Entry> {
+    let content = read_from_file(DAILY_FILE).unwrap_or_default();
+    content.lines().filter_map(|line| {
+        let values = line.split_whitespace().collect::<Vec<_>>();
+        match values[..] {
+            [date, profile, score] if is_valid_profile(profile) => Some(Entry {
+                date: Date::parse(date)?,
+                profile: profile.to_string(),
+                score: score.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }).collect()
+}
+
+fn save(entries: &[Entry]) -> io::Result<()> {
+    let content = entries.iter()
+                         .map(|entry| format!("{} {} {}\n", entry.date, entry.profile, entry.score))
+                         .collect::<String>();
+    write_into_file(&content, DAILY_FILE)
+}
+
+/// Whether `profile` already had its attempt at the challenge of `date`.
+pub fn has_played(date: Date, profile: &str) -> bool {
+    load().iter().any(|entry| entry.date == date && entry.profile == profile)
+}
+
+/// Enters the score of `profile` at the challenge of `date`, replacing the one
+/// it had. The attempt is entered with a score of 0 as soon as its game
+/// starts, so that quitting doesn't give another try.
+pub fn save_attempt(date: Date, profile: &str, score: u32) -> io::Result<()> {
+    let mut entries = load();
+    match entries.iter_mut().find(|entry| entry.date == date && entry.profile == profile) {
+        Some(entry) => entry.score = score,
+        None => entries.push(Entry { date, profile: profile.to_string(), score }),
+    }
+    save(&entries)
+}
+
+/// The attempts of `date`, best first.
+pub fn leaderboard(entries: &[Entry], date: Date) -> Vec<&Entry> {
+    let mut day = entries.iter().filter(|entry| entry.date == date).collect::<Vec<_>>();
+    day.sort_by_key(|entry| Reverse(entry.score));
+    day
+}
+
+/// Prints the leaderboard of `date`, pointing out `profile`, and the winners of
+/// the days before.
+pub fn print_leaderboards(date: Date, profile: Option<&str>) {
+    let entries = load();
+    println!("Daily challenge of {}:", date);
+    let day = leaderboard(&entries, date);
+    if day.is_empty() {
+        println!("    nobody played it yet");
+    }
+    for (rank, entry) in day.iter().enumerate() {
+        let you = if Some(entry.profile.as_str()) == profile { " <" } else { "" };
+        println!("{:4}. {:<width$} {:>8}{}", rank + 1, entry.profile, entry.score, you,
+                 width = MAX_PROFILE_LEN);
+    }
+    let mut days = entries.iter().map(|entry| entry.date).filter(|&day| day < date).collect::<Vec<_>>();
+    days.sort();
+    days.dedup();
+    if days.is_empty() {
+        return
+    }
+    println!("Previous days:");
+    for &day in days.iter().rev().take(NB_HISTORY_DAYS) {
+        let board = leaderboard(&entries, day);
+        let players = if board.len() == 1 { "player" } else { "players" };
+        println!("    {} {:<width$} {:>8} ({} {})", day, board[0].profile, board[0].score,
+                 board.len(), players, width = MAX_PROFILE_LEN);
+    }
+}
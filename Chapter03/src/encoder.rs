@@ -0,0 +1,545 @@
+// Just enough of the PNG, APNG and GIF formats to save screenshots and replays
+// without pulling in an image library. The compression is simple but the
+// pictures of the game are mostly flat colours, which it handles well.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// An RGB picture, 3 bytes per pixel, line after line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Part of a picture, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Area {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Image {
+    fn pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        let i = ((y * self.width + x) * 3) as usize;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]]
+    }
+
+    fn whole(&self) -> Area {
+        Area { x: 0, y: 0, width: self.width, height: self.height }
+    }
+
+    /// The smallest area holding every pixel which differs from `previous`.
+    fn changes_since(&self, previous: &Image) -> Option<Area> {
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (self.width, self.height, 0, 0);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.pixel(x, y) != previous.pixel(x, y) {
+                    min_x = min_x.min(x);
+                    min_y = min_y.min(y);
+                    max_x = max_x.max(x);
+                    max_y = max_y.max(y);
+                }
+            }
+        }
+        if min_x > max_x {
+            return None;
+        }
+        Some(Area { x: min_x, y: min_y, width: max_x - min_x + 1, height: max_y - min_y + 1 })
+    }
+}
+
+// Compression
+
+/// Writes bits, least significant first, as deflate and GIF want them.
+struct BitWriter {
+    bytes: Vec<u8>,
+    current: u32,
+    nb_bits: u32,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter { bytes: Vec::new(), current: 0, nb_bits: 0 }
+    }
+
+    fn write(&mut self, value: u32, nb_bits: u32) {
+        self.current |= value << self.nb_bits;
+        self.nb_bits += nb_bits;
+        while self.nb_bits >= 8 {
+            self.bytes.push(self.current as u8);
+            self.current >>= 8;
+            self.nb_bits -= 8;
+        }
+    }
+
+    /// Huffman codes are written most significant bit first.
+    fn write_code(&mut self, code: u32, nb_bits: u32) {
+        let reversed = (0..nb_bits).fold(0, |acc, i| (acc << 1) | ((code >> i) & 1));
+        self.write(reversed, nb_bits);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.nb_bits > 0 {
+            self.bytes.push(self.current as u8);
+        }
+        self.bytes
+    }
+}
+
+const LENGTH_BASES: [u32; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51,
+                                 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA_BITS: [u32; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4,
+                                      4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASES: [u32; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257,
+                                   385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289,
+                                   16385, 24577];
+const DISTANCE_EXTRA_BITS: [u32; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8,
+                                        9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+const WINDOW_SIZE: usize = 32768;
+const MAX_MATCH: usize = 258;
+
+/// Writes a literal or length symbol with the fixed Huffman codes of deflate.
+fn write_symbol(bits: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => bits.write_code(0x30 + symbol, 8),
+        144..=255 => bits.write_code(0x190 + symbol - 144, 9),
+        256..=279 => bits.write_code(symbol - 256, 7),
+        _ => bits.write_code(0xC0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(bits: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASES.iter().rposition(|&base| base as usize <= length).expect("match too short");
+    write_symbol(bits, 257 + code as u32);
+    bits.write(length as u32 - LENGTH_BASES[code], LENGTH_EXTRA_BITS[code]);
+    let code = DISTANCE_BASES.iter().rposition(|&base| base as usize <= distance).expect("no distance");
+    bits.write_code(code as u32, 5);
+    bits.write(distance as u32 - DISTANCE_BASES[code], DISTANCE_EXTRA_BITS[code]);
+}
+
+/// Compresses `data` as a zlib stream, with a single block using the fixed
+/// Huffman codes and the last match found for every 3 bytes.
+fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::new();
+    // Final block, fixed codes.
+    bits.write(1, 1);
+    bits.write(1, 2);
+    let mut last_seen: HashMap<[u8; 3], usize> = HashMap::new();
+    let mut i = 0;
+    while i < data.len() {
+        let mut length = 0;
+        let mut distance = 0;
+        if i + 3 <= data.len() {
+            let key = [data[i], data[i + 1], data[i + 2]];
+            if let Some(&candidate) = last_seen.get(&key) {
+                if i - candidate <= WINDOW_SIZE {
+                    let max = MAX_MATCH.min(data.len() - i);
+                    length = (0..max).take_while(|&n| data[candidate + n] == data[i + n]).count();
+                    distance = i - candidate;
+                }
+            }
+            last_seen.insert(key, i);
+        }
+        if length >= 3 {
+            write_match(&mut bits, length, distance);
+            for j in i + 1..(i + length).min(data.len().saturating_sub(2)) {
+                last_seen.insert([data[j], data[j + 1], data[j + 2]], j);
+            }
+            i += length;
+        } else {
+            write_symbol(&mut bits, data[i] as u32);
+            i += 1;
+        }
+    }
+    write_symbol(&mut bits, 256);
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    let mut stream = vec![0x78, 0x01];
+    stream.extend(bits.finish());
+    stream.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    stream
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+// PNG and APNG
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut chunk = kind.to_vec();
+    chunk.extend_from_slice(data);
+    out.write_all(&chunk)?;
+    out.write_all(&crc32(&chunk).to_be_bytes())
+}
+
+fn write_header<W: Write>(out: &mut W, image: &Image) -> io::Result<()> {
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+    let mut header = Vec::new();
+    header.extend_from_slice(&image.width.to_be_bytes());
+    header.extend_from_slice(&image.height.to_be_bytes());
+    // 8 bits per channel, RGB, no interlacing.
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(out, b"IHDR", &header)
+}
+
+/// The compressed pixels of an area of `image`. Every line but the first is
+/// stored as its difference with the line above ("up" filter).
+fn png_data(image: &Image, area: Area) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(((area.width * 3 + 1) * area.height) as usize);
+    let line = |y: u32| {
+        let start = ((y * image.width + area.x) * 3) as usize;
+        &image.pixels[start..start + (area.width * 3) as usize]
+    };
+    for y in area.y..area.y + area.height {
+        if y == area.y {
+            raw.push(0);
+            raw.extend_from_slice(line(y));
+        } else {
+            raw.push(2);
+            raw.extend(line(y).iter().zip(line(y - 1)).map(|(&a, &b)| a.wrapping_sub(b)));
+        }
+    }
+    zlib_compress(&raw)
+}
+
+pub fn write_png(image: &Image, file_name: &str) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(file_name)?);
+    write_header(&mut out, image)?;
+    write_chunk(&mut out, b"IDAT", &png_data(image, image.whole()))?;
+    write_chunk(&mut out, b"IEND", &[])?;
+    out.flush()
+}
+
+/// Encoders of animations. Frames are kept in memory, compressed, until the
+/// animation is written.
+pub trait Animation {
+    /// Adds a frame shown for `delay` milliseconds.
+    fn add_frame(&mut self, image: Image, delay: u32);
+    fn write(&self, file_name: &str) -> io::Result<()>;
+}
+
+/// A frame which only redraws part of the picture.
+struct Frame {
+    area: Area,
+    data: Vec<u8>,
+    /// In milliseconds.
+    delay: u32,
+}
+
+#[derive(Default)]
+pub struct Apng {
+    first: Option<Image>,
+    previous: Option<Image>,
+    frames: Vec<Frame>,
+}
+
+impl Animation for Apng {
+    fn add_frame(&mut self, image: Image, delay: u32) {
+        let area = match self.previous {
+            // Frames which don't change anything only make the previous one last.
+            Some(ref previous) => match image.changes_since(previous) {
+                Some(area) => area,
+                None => {
+                    self.frames.last_mut().expect("no frame").delay += delay;
+                    return
+                }
+            },
+            None => image.whole(),
+        };
+        self.frames.push(Frame { area, data: png_data(&image, area), delay });
+        if self.first.is_none() {
+            self.first = Some(image.clone());
+        }
+        self.previous = Some(image);
+    }
+
+    fn write(&self, file_name: &str) -> io::Result<()> {
+        let first = match self.first {
+            Some(ref first) => first,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "no frame to write")),
+        };
+        let mut out = BufWriter::new(File::create(file_name)?);
+        write_header(&mut out, first)?;
+        let mut control = Vec::new();
+        control.extend_from_slice(&(self.frames.len() as u32).to_be_bytes());
+        // Played in a loop.
+        control.extend_from_slice(&0u32.to_be_bytes());
+        write_chunk(&mut out, b"acTL", &control)?;
+
+        let mut sequence = 0u32;
+        for (i, frame) in self.frames.iter().enumerate() {
+            let mut control = sequence.to_be_bytes().to_vec();
+            for value in &[frame.area.width, frame.area.height, frame.area.x, frame.area.y] {
+                control.extend_from_slice(&value.to_be_bytes());
+            }
+            control.extend_from_slice(&(frame.delay.min(u16::MAX as u32) as u16).to_be_bytes());
+            control.extend_from_slice(&1000u16.to_be_bytes());
+            // Nothing gets disposed of and the area is replaced.
+            control.extend_from_slice(&[0, 0]);
+            write_chunk(&mut out, b"fcTL", &control)?;
+            sequence += 1;
+            // The first frame is also the picture shown by viewers without APNG
+            // support.
+            if i == 0 {
+                write_chunk(&mut out, b"IDAT", &frame.data)?;
+            } else {
+                let mut data = sequence.to_be_bytes().to_vec();
+                data.extend_from_slice(&frame.data);
+                write_chunk(&mut out, b"fdAT", &data)?;
+                sequence += 1;
+            }
+        }
+        write_chunk(&mut out, b"IEND", &[])?;
+        out.flush()
+    }
+}
+
+// GIF
+
+const MAX_GIF_CODE: u16 = 4095;
+
+/// Compresses colour indexes with the variant of LZW used by GIF.
+fn lzw_compress(indexes: &[u8], min_code_size: u32) -> Vec<u8> {
+    let clear_code = 1u16 << min_code_size;
+    let mut bits = BitWriter::new();
+    let mut codes: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = min_code_size + 1;
+    let mut max_code = clear_code + 1;
+    bits.write(clear_code as u32, code_size);
+
+    let mut current = match indexes.first() {
+        Some(&index) => index as u16,
+        None => {
+            bits.write(clear_code as u32 + 1, code_size);
+            return bits.finish()
+        }
+    };
+    for &index in &indexes[1..] {
+        if let Some(&code) = codes.get(&(current, index)) {
+            current = code;
+            continue
+        }
+        bits.write(current as u32, code_size);
+        max_code += 1;
+        codes.insert((current, index), max_code);
+        if max_code >= 1 << code_size {
+            code_size += 1;
+        }
+        if max_code == MAX_GIF_CODE {
+            bits.write(clear_code as u32, code_size);
+            codes.clear();
+            code_size = min_code_size + 1;
+            max_code = clear_code + 1;
+        }
+        current = index as u16;
+    }
+    bits.write(current as u32, code_size);
+    // Reading the last code, the decoder adds the entry the encoder would have
+    // added with the next one, which can make the end code a bit longer.
+    if max_code + 1 >= 1 << code_size {
+        code_size += 1;
+    }
+    bits.write(clear_code as u32 + 1, code_size);
+    bits.finish()
+}
+
+/// Colour indexes of an area of `image` and the colours they refer to. Pictures
+/// with more than 256 colours get the nearest colours of a fixed palette.
+fn gif_palette(image: &Image, area: Area) -> (Vec<u8>, Vec<[u8; 3]>) {
+    let mut palette = Vec::new();
+    let mut indexes = Vec::with_capacity((area.width * area.height) as usize);
+    let mut known = HashMap::new();
+    'pixels: for y in area.y..area.y + area.height {
+        for x in area.x..area.x + area.width {
+            let color = image.pixel(x, y);
+            let index = *known.entry(color).or_insert_with(|| {
+                palette.push(color);
+                palette.len() - 1
+            });
+            if palette.len() > 256 {
+                break 'pixels
+            }
+            indexes.push(index as u8);
+        }
+    }
+    if palette.len() <= 256 {
+        return (indexes, palette);
+    }
+    // 6 levels of red and blue and 7 of green.
+    let level = |value: u8, levels: u32| (value as u32 * (levels - 1) + 127) / 255;
+    let indexes = (area.y..area.y + area.height).flat_map(|y| {
+        (area.x..area.x + area.width).map(move |x| (x, y))
+    }).map(|(x, y)| {
+        let [r, g, b] = image.pixel(x, y);
+        (level(r, 6) * 42 + level(g, 7) * 6 + level(b, 6)) as u8
+    }).collect();
+    let value = |level: u32, levels: u32| (level * 255 / (levels - 1)) as u8;
+    let palette = (0..252).map(|i| [value(i / 42, 6), value(i / 6 % 7, 7), value(i % 6, 6)]).collect();
+    (indexes, palette)
+}
+
+#[derive(Default)]
+pub struct Gif {
+    size: Option<(u32, u32)>,
+    previous: Option<Image>,
+    frames: Vec<Frame>,
+    /// The colour table of each frame and its size, as a power of 2.
+    palettes: Vec<(u32, Vec<[u8; 3]>)>,
+}
+
+impl Animation for Gif {
+    fn add_frame(&mut self, image: Image, delay: u32) {
+        let area = match self.previous {
+            Some(ref previous) => match image.changes_since(previous) {
+                Some(area) => area,
+                None => {
+                    self.frames.last_mut().expect("no frame").delay += delay;
+                    return
+                }
+            },
+            None => image.whole(),
+        };
+        let (indexes, mut palette) = gif_palette(&image, area);
+        // Colour tables hold a power of 2 of colours, 2 at least.
+        let table_bits = (1..=8).find(|&bits| palette.len() <= 1 << bits).expect("too many colours");
+        palette.resize(1 << table_bits, [0, 0, 0]);
+        let data = lzw_compress(&indexes, table_bits.max(2));
+        self.frames.push(Frame { area, data, delay });
+        self.palettes.push((table_bits, palette));
+        self.size = self.size.or(Some((image.width, image.height)));
+        self.previous = Some(image);
+    }
+
+    fn write(&self, file_name: &str) -> io::Result<()> {
+        let (width, height) = match self.size {
+            Some(size) => size,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "no frame to write")),
+        };
+        let mut out = BufWriter::new(File::create(file_name)?);
+        out.write_all(b"GIF89a")?;
+        out.write_all(&(width as u16).to_le_bytes())?;
+        out.write_all(&(height as u16).to_le_bytes())?;
+        // No global colour table, every frame has its own.
+        out.write_all(&[0, 0, 0])?;
+        // Played in a loop.
+        out.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+
+        for (frame, &(table_bits, ref palette)) in self.frames.iter().zip(&self.palettes) {
+            // GIF delays are in hundredths of a second.
+            let delay = ((frame.delay + 5) / 10).min(u16::MAX as u32) as u16;
+            out.write_all(&[0x21, 0xF9, 4, 0x04])?;
+            out.write_all(&delay.to_le_bytes())?;
+            out.write_all(&[0, 0])?;
+
+            out.write_all(&[0x2C])?;
+            for value in &[frame.area.x, frame.area.y, frame.area.width, frame.area.height] {
+                out.write_all(&(*value as u16).to_le_bytes())?;
+            }
+            out.write_all(&[0x80 | (table_bits - 1) as u8])?;
+            for color in palette {
+                out.write_all(color)?;
+            }
+            out.write_all(&[table_bits.max(2) as u8])?;
+            // The compressed data comes in blocks of 255 bytes at most.
+            for block in frame.data.chunks(255) {
+                out.write_all(&[block.len() as u8])?;
+                out.write_all(block)?;
+            }
+            out.write_all(&[0])?;
+        }
+        out.write_all(&[0x3B])?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lzw_compress, MAX_GIF_CODE};
+
+    /// Decodes the output of `lzw_compress` the way GIF readers do, panicking
+    /// on codes they would reject.
+    fn lzw_decompress(data: &[u8], min_code_size: u32) -> Vec<u8> {
+        let clear_code = 1usize << min_code_size;
+        let reset = || (0..clear_code + 2).map(|i| vec![i as u8]).collect::<Vec<_>>();
+        let mut table = reset();
+        let mut code_size = min_code_size + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut output = Vec::new();
+        let mut position = 0;
+        loop {
+            let code = (0..code_size).fold(0, |code, i| {
+                let bit = position + i as usize;
+                assert!(bit / 8 < data.len(), "no end code");
+                code | (((data[bit / 8] >> (bit % 8)) & 1) as usize) << i
+            });
+            position += code_size as usize;
+            if code == clear_code {
+                table = reset();
+                code_size = min_code_size + 1;
+                previous = None;
+                continue
+            }
+            if code == clear_code + 1 {
+                return output
+            }
+            let entry = match previous {
+                _ if code < table.len() => table[code].clone(),
+                Some(ref previous) if code == table.len() => {
+                    let mut entry = previous.clone();
+                    entry.push(previous[0]);
+                    entry
+                }
+                _ => panic!("bad code {}, next {} size {}", code, table.len(), code_size),
+            };
+            output.extend_from_slice(&entry);
+            if let Some(mut previous) = previous.take() {
+                if table.len() <= MAX_GIF_CODE as usize {
+                    previous.push(entry[0]);
+                    table.push(previous);
+                }
+            }
+            if table.len() == 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        let mut state = 0x2545_f491_u32;
+        for nb in 0..600 {
+            let min_code_size = 2 + nb % 7;
+            let nb_colors = 1u32 << min_code_size;
+            let len = nb * 37 % 9000;
+            let indexes = (0..len).map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                // Few colours most of the time, so that the table fills at
+                // different rates.
+                (state % (nb_colors >> ((state >> 29) % min_code_size))) as u8
+            }).collect::<Vec<_>>();
+            let data = lzw_compress(&indexes, min_code_size);
+            assert_eq!(lzw_decompress(&data, min_code_size), indexes, "stream {}", nb);
+        }
+    }
+}
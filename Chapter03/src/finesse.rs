@@ -0,0 +1,226 @@
+// Finesse is about placing every piece with as few inputs as possible. The
+// shortest input sequences come from a breadth-first search of the positions the
+// piece can reach from where it spawned, and the player's inputs are counted the
+// same way: holding a direction until the piece hits something (DAS) or holding
+// the soft drop counts as a single input, and the drop locking the piece is free.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use board::Board;
+use tetrimino::Tetrimino;
+use tetris::Action;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Input {
+    Left,
+    Right,
+    /// Left held until the piece can't move anymore.
+    DasLeft,
+    DasRight,
+    Rotate,
+    /// Soft drop held until the piece lands, to slide or rotate it afterwards.
+    SoftDrop,
+}
+
+const INPUTS: [Input; 6] = [Input::Left, Input::Right, Input::DasLeft, Input::DasRight,
+                            Input::Rotate, Input::SoftDrop];
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Input::Left => "left",
+            Input::Right => "right",
+            Input::DasLeft => "DAS left",
+            Input::DasRight => "DAS right",
+            Input::Rotate => "rotate",
+            Input::SoftDrop => "soft drop",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Formats inputs as a comma-separated list.
+pub fn format_inputs(inputs: &[Input]) -> String {
+    if inputs.is_empty() {
+        return "none".to_string();
+    }
+    inputs.iter().map(|input| input.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// Position of a piece: x, y and rotation state.
+type Position = (isize, usize, u8);
+/// Cells of the map covered by a locked piece, sorted.
+type Cells = Vec<(isize, usize)>;
+
+fn cells(piece: &Tetrimino, (x, y, state): Position) -> Cells {
+    let mut cells = Vec::new();
+    for (shift_y, line) in piece.states[state as usize].iter().enumerate() {
+        for (shift_x, &cell) in line.iter().enumerate() {
+            if cell != 0 {
+                cells.push((x + shift_x as isize, y + shift_y));
+            }
+        }
+    }
+    cells.sort();
+    cells
+}
+
+/// Where `input` takes the piece from `position`, if it moves it.
+fn step(game_map: &Board, piece: &mut Tetrimino, position: Position,
+        input: Input) -> Option<Position> {
+    let (x, y, state) = position;
+    if input == Input::Rotate {
+        piece.x = x;
+        piece.y = y;
+        piece.current_state = state;
+        return if piece.rotate(game_map) {
+            Some((piece.x, piece.y, piece.current_state))
+        } else {
+            None
+        };
+    }
+    let fits = |x, y| piece.test_position(game_map, state as usize, x, y);
+    // Holding an input only differs from tapping it if the piece moves twice.
+    let slide = |dx: isize, min: usize| {
+        let mut moved = 0;
+        while fits(x + (moved as isize + 1) * dx, y) {
+            moved += 1;
+        }
+        if moved >= min { Some((x + moved as isize * dx, y, state)) } else { None }
+    };
+    match input {
+        Input::Left => slide(-1, 1).map(|_| (x - 1, y, state)),
+        Input::Right => slide(1, 1).map(|_| (x + 1, y, state)),
+        Input::DasLeft => slide(-1, 2),
+        Input::DasRight => slide(1, 2),
+        Input::SoftDrop => {
+            let mut landing = y;
+            while fits(x, landing + 1) {
+                landing += 1;
+            }
+            if landing > y { Some((x, landing, state)) } else { None }
+        }
+        Input::Rotate => unreachable!(),
+    }
+}
+
+/// The shortest input sequence giving each placement of `piece`, starting
+/// where it is.
+pub fn optimal_inputs(game_map: &Board, piece: &Tetrimino) -> HashMap<Cells, Vec<Input>> {
+    let mut piece = piece.clone();
+    let start = (piece.x, piece.y, piece.current_state);
+    let mut paths: HashMap<Position, Vec<Input>> = HashMap::new();
+    let mut placements = HashMap::new();
+    let mut queue = VecDeque::new();
+    paths.insert(start, Vec::new());
+    queue.push_back(start);
+
+    // Positions are visited by increasing number of inputs, so the first path
+    // found to a placement is one of the shortest.
+    while let Some(position) = queue.pop_front() {
+        let path = paths[&position].clone();
+        let (x, mut landing, state) = position;
+        while piece.test_position(game_map, state as usize, x, landing + 1) {
+            landing += 1;
+        }
+        placements.entry(cells(&piece, (x, landing, state))).or_insert_with(|| path.clone());
+        for &input in &INPUTS {
+            if let Some(next) = step(game_map, &mut piece, position, input) {
+                if let Entry::Vacant(entry) = paths.entry(next) {
+                    let mut next_path = path.clone();
+                    next_path.push(input);
+                    entry.insert(next_path);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    placements
+}
+
+/// A piece placed with more inputs than needed.
+#[derive(Clone, Debug)]
+pub struct Fault {
+    pub used: Vec<Input>,
+    pub optimal: Vec<Input>,
+}
+
+/// Follows the inputs of the player for the current piece.
+#[derive(Clone, Debug, Default)]
+pub struct Finesse {
+    /// The piece as it spawned and the map it spawned in.
+    start: Option<(Tetrimino, Board)>,
+    /// The actions applied to the piece, with whether they left it blocked in
+    /// their direction.
+    actions: Vec<(Action, bool)>,
+    pub last_fault: Option<Fault>,
+}
+
+impl Finesse {
+    pub fn piece_spawned(&mut self, piece: &Tetrimino, game_map: &Board) {
+        self.start = Some((piece.clone(), game_map.clone()));
+        self.actions.clear();
+    }
+
+    /// `blocked` tells whether the piece can still move in the direction of
+    /// `action` afterwards.
+    pub fn action(&mut self, action: Action, blocked: bool) {
+        if action != Action::HardDrop {
+            self.actions.push((action, blocked));
+        }
+    }
+
+    /// Turns the actions into inputs, the way a player would have typed them.
+    fn player_inputs(&self) -> Vec<Input> {
+        let mut inputs = Vec::new();
+        let mut i = 0;
+        while i < self.actions.len() {
+            let (action, _) = self.actions[i];
+            let run = self.actions[i..].iter().take_while(|&&(a, _)| a == action).count();
+            let blocked = self.actions[i + run - 1].1;
+            match action {
+                Action::MoveLeft | Action::MoveRight => {
+                    let (tap, das) = if action == Action::MoveLeft {
+                        (Input::Left, Input::DasLeft)
+                    } else {
+                        (Input::Right, Input::DasRight)
+                    };
+                    if run > 1 && blocked {
+                        inputs.push(das);
+                    } else {
+                        inputs.extend((0..run).map(|_| tap));
+                    }
+                }
+                Action::Rotate => inputs.extend((0..run).map(|_| Input::Rotate)),
+                // Soft drops only matter if something is done afterwards.
+                Action::SoftDrop if i + run < self.actions.len() => inputs.push(Input::SoftDrop),
+                Action::SoftDrop | Action::HardDrop | Action::Hold => {}
+            }
+            i += run;
+        }
+        inputs
+    }
+
+    /// Checks the inputs used to bring `piece` where it got locked. Returns
+    /// `true` if it was a finesse fault.
+    pub fn piece_locked(&mut self, piece: &Tetrimino) -> bool {
+        let placed = cells(piece, (piece.x, piece.y, piece.current_state));
+        let (start, game_map) = match self.start.take() {
+            Some(start) => start,
+            // The game was continued from a save with this piece in play.
+            None => return false,
+        };
+        let used = self.player_inputs();
+        match optimal_inputs(&game_map, &start).remove(&placed) {
+            Some(optimal) if optimal.len() < used.len() => {
+                self.last_fault = Some(Fault { used, optimal });
+                true
+            }
+            // Either no fault, or the map changed under the piece (rising
+            // garbage) and there's nothing to compare with.
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use std::mem;
+use std::time::Duration;
+
+use ai::{self, Weights};
+use classic;
+use mode::{GameMode, GameOver, Outcome};
+#[cfg(feature = "scripting")]
+use script::Script;
+use tetris::{Action, Event, Tetris};
+
+/// Length of a simulation step. The game advances by whole ticks whatever the
+/// frame rate, so that it plays the same on every machine.
+pub const TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Bounds of `Timing::speed`.
+pub const MIN_SPEED: u32 = 10;
+pub const MAX_SPEED: u32 = 200;
+
+/// Delays between the lock of a piece and the spawn of the next one, and the
+/// speed of the falling pieces.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timing {
+    /// "Appearance delay", after every piece. The classic mode has its own.
+    pub are: Duration,
+    /// Added to the ARE when lines got cleared. The classic mode only has the
+    /// entry delay of the NES.
+    pub line_clear_delay: Duration,
+    /// Speed of the falling pieces in percent, 100 being the speed of the level.
+    pub speed: u32,
+}
+
+impl Default for Timing {
+    fn default() -> Timing {
+        Timing {
+            are: Duration::from_millis(0),
+            line_clear_delay: Duration::from_millis(300),
+            speed: 100,
+        }
+    }
+}
+
+/// A game being played: the engine and the timers driving it. The timers count
+/// simulated time, not wall-clock time.
+pub struct Game {
+    pub tetris: Tetris,
+    /// Time played so far.
+    pub time: Duration,
+    /// Time since the current piece last fell.
+    pub fall_timer: Duration,
+    /// Time since the last rising garbage line.
+    pub garbage_timer: Duration,
+    pub timing: Timing,
+    /// Whether the game counts for the high scores, which it doesn't once it
+    /// has been played with other timings than the default ones.
+    pub ranked: bool,
+    /// Time left before the next piece comes.
+    pub spawn_delay: Duration,
+    /// ARE of the last locked piece, which the line-clear delay comes after.
+    are: Duration,
+    /// Whether the game is in the line-clear part of `spawn_delay`.
+    pub clearing: bool,
+    /// Rotations and holds made while waiting for the next piece, applied to it
+    /// when it spawns in the modes which allow it.
+    initial_actions: Vec<Action>,
+    /// Actions whose keys are held down, set with `set_held`.
+    held: Vec<Action>,
+    events: Vec<Event>,
+    /// Script of the mod being played, started with `Script::start`.
+    #[cfg(feature = "scripting")]
+    pub script: Option<Script>,
+}
+
+impl Game {
+    pub fn new(tetris: Tetris) -> Game {
+        Game {
+            tetris,
+            time: Duration::new(0, 0),
+            fall_timer: Duration::new(0, 0),
+            garbage_timer: Duration::new(0, 0),
+            timing: Timing::default(),
+            ranked: true,
+            spawn_delay: Duration::new(0, 0),
+            are: Duration::new(0, 0),
+            clearing: false,
+            initial_actions: Vec::new(),
+            held: Vec::new(),
+            events: Vec::new(),
+            #[cfg(feature = "scripting")]
+            script: None,
+        }
+    }
+
+    /// Advances the game by one tick, applying the player's `actions` (or the
+    /// bot's moves). Returns the outcome of the game once it's over.
+    pub fn tick(&mut self, actions: &[Action], bot: Option<&Weights>) -> Option<Outcome> {
+        let outcome = self.step(actions, bot);
+        if let Some(Outcome::Lost(reason)) = outcome {
+            self.tetris.notify_game_over(reason);
+        }
+        outcome
+    }
+
+    /// Changes the timing of the game, making it unranked if it isn't the
+    /// default one.
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.ranked &= timing == Timing::default();
+        self.timing = timing;
+    }
+
+    /// Sets the actions whose keys are held down. The rotation and the hold
+    /// among them are applied to the next piece as it spawns, like the ones
+    /// pressed while waiting for it, so that a key held since before the last
+    /// piece locked counts too.
+    pub fn set_held(&mut self, held: &[Action]) {
+        self.held = held.to_vec();
+    }
+
+    fn step(&mut self, actions: &[Action], bot: Option<&Weights>) -> Option<Outcome> {
+        self.time += TICK;
+        let fall_delay = self.fall_delay();
+        let tetris = &mut self.tetris;
+
+        if let Some(interval) = tetris.mode.rising_garbage() {
+            self.garbage_timer += TICK;
+            if self.garbage_timer >= interval {
+                self.garbage_timer = Duration::new(0, 0);
+                if !tetris.add_garbage(1) {
+                    return Some(Outcome::Lost(GameOver::GarbageTopOut));
+                }
+            }
+        }
+
+        self.fall_timer += TICK;
+        if self.fall_timer >= fall_delay {
+            tetris.fall();
+            self.fall_timer = Duration::new(0, 0);
+        }
+        self.collect_events();
+        if self.tetris.game_over.is_some() {
+            return self.outcome();
+        }
+        let tetris = &mut self.tetris;
+
+        if tetris.current_piece.is_none() {
+            self.spawn_delay = self.spawn_delay.checked_sub(TICK).unwrap_or_default();
+        }
+        if tetris.current_piece.is_none() && self.spawn_delay == Duration::new(0, 0) {
+            self.clearing = false;
+            self.fall_timer = Duration::new(0, 0);
+            let mut initial = mem::take(&mut self.initial_actions);
+            if tetris.mode.has_initial_actions() {
+                initial.extend(&self.held);
+            }
+            if !tetris.spawn_piece(&initial) {
+                // The last piece of a puzzle may just have reached its goal.
+                return Some(self.outcome().unwrap_or(Outcome::Lost(GameOver::OutOfPieces)));
+            }
+            if let Some(weights) = bot {
+                ai::move_piece(tetris, weights);
+            }
+        }
+        if tetris.current_piece.is_none() {
+            if tetris.mode.has_initial_actions() {
+                self.initial_actions.extend(actions.iter().filter(|&&action| {
+                    action == Action::Rotate || action == Action::Hold
+                }));
+            }
+        } else {
+            self.apply_actions(actions);
+        }
+        let script_outcome = self.run_script();
+        self.collect_events();
+        script_outcome.or_else(|| self.outcome())
+    }
+
+    #[cfg(feature = "scripting")]
+    fn run_script(&mut self) -> Option<Outcome> {
+        let script = self.script.as_mut()?;
+        script.tick(&mut self.tetris, self.time)
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    fn run_script(&mut self) -> Option<Outcome> {
+        None
+    }
+
+    /// Information displayed next to the game map: the one of the mode, and the
+    /// error which stopped the script of a mod.
+    pub fn hud(&self) -> Vec<(&'static str, String)> {
+        let mut hud = self.tetris.mode.hud(&self.tetris, self.time);
+        if let Some(error) = self.script_error() {
+            hud.push(("Mod error", error));
+        }
+        hud
+    }
+
+    #[cfg(feature = "scripting")]
+    fn script_error(&self) -> Option<String> {
+        self.script.as_ref().and_then(Script::error)
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    fn script_error(&self) -> Option<String> {
+        None
+    }
+
+    /// The outcome of the game if it's over. Reaching the goal of the mode wins
+    /// even if the same piece topped out.
+    fn outcome(&self) -> Option<Outcome> {
+        self.tetris.mode.check(&self.tetris, self.time)
+                        .or_else(|| self.tetris.game_over.map(Outcome::Lost))
+    }
+
+    fn apply_actions(&mut self, actions: &[Action]) {
+        let tetris = &mut self.tetris;
+        for &action in actions {
+            if action == Action::SoftDrop {
+                self.fall_timer = Duration::new(0, 0);
+            }
+            if tetris.apply_action(action) {
+                // The other actions were meant for the piece which just got locked
+                // or held.
+                self.fall_timer = Duration::new(0, 0);
+                break
+            }
+        }
+    }
+
+    /// Keeps the events of the engine for the frontends and starts the delays
+    /// following a lock.
+    fn collect_events(&mut self) {
+        for event in self.tetris.take_events() {
+            if let Event::Locked { ref rows, .. } = event {
+                self.clearing = !rows.is_empty();
+                let (are, line_clear_delay) = match self.tetris.mode {
+                    GameMode::Classic { .. } => {
+                        (classic::entry_delay(self.tetris.lock_height), Duration::new(0, 0))
+                    }
+                    _ => (self.timing.are, self.timing.line_clear_delay),
+                };
+                self.are = are;
+                self.spawn_delay = are;
+                if self.clearing {
+                    self.spawn_delay += line_clear_delay;
+                }
+            }
+            self.events.push(event);
+        }
+    }
+
+    pub fn take_events(&mut self) -> Vec<Event> {
+        mem::take(&mut self.events)
+    }
+
+    /// How far the line-clear delay is, from 0 to 1, if lines are being cleared.
+    pub fn clear_progress(&self) -> Option<f64> {
+        let delay = self.timing.line_clear_delay;
+        if !self.clearing || delay == Duration::new(0, 0) || self.spawn_delay <= self.are {
+            return None;
+        }
+        let left = self.spawn_delay - self.are;
+        Some(1. - left.as_secs_f64() / delay.as_secs_f64())
+    }
+
+    /// How far the current piece is on its way to the next line, from 0 to 1.
+    pub fn fall_progress(&self) -> f64 {
+        (self.fall_timer.as_secs_f64() / self.fall_delay().as_secs_f64()).min(1.)
+    }
+
+    /// Time the current piece takes to fall one line, at the speed of the game.
+    fn fall_delay(&self) -> Duration {
+        Duration::from_millis(self.tetris.fall_delay() as u64 * 100 / self.timing.speed.max(1) as u64)
+    }
+}
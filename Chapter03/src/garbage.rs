@@ -0,0 +1,71 @@
+use randomizer::XorShift;
+use save::{self, parse};
+
+/// Value of the garbage cells in the game map, out of the way of the pieces'.
+pub const GARBAGE: u8 = u8::MAX;
+pub const GARBAGE_COLOR: (u8, u8, u8) = (128, 128, 128);
+
+#[derive(Clone, Debug)]
+pub struct GarbageGenerator {
+    rng: XorShift,
+    messy: bool,
+    previous_hole: Option<usize>,
+}
+
+impl GarbageGenerator {
+    pub fn new(seed: u64, messy: bool) -> GarbageGenerator {
+        GarbageGenerator {
+            rng: XorShift::new(seed),
+            messy,
+            previous_hole: None,
+        }
+    }
+
+    /// Formats the generator as "STATE MESSY PREVIOUS_HOLE".
+    pub fn to_save(&self) -> String {
+        let hole = self.previous_hole.map(|hole| hole.to_string()).unwrap_or_else(|| "none".to_string());
+        format!("{} {} {}", self.rng.state(), self.messy, hole)
+    }
+
+    pub fn from_save(s: &str) -> save::Result<GarbageGenerator> {
+        let values = s.split_whitespace().collect::<Vec<_>>();
+        if values.len() != 3 {
+            return Err("invalid garbage generator".into());
+        }
+        Ok(GarbageGenerator {
+            rng: XorShift::from_state(parse(values[0], "generator state")?)?,
+            messy: parse(values[1], "garbage messiness")?,
+            previous_hole: match values[2] {
+                "none" => None,
+                hole => Some(parse(hole, "garbage hole")?),
+            },
+        })
+    }
+
+    /// Creates a full line with holes in it: a single one whose column changes
+    /// from line to line, or a few random ones if the garbage is messy.
+    pub fn next_line(&mut self, width: usize) -> Vec<u8> {
+        let mut line = vec![GARBAGE; width];
+        if self.messy {
+            for cell in line.iter_mut() {
+                if self.rng.next_f64() < 0.3 {
+                    *cell = 0;
+                }
+            }
+            if line.iter().all(|&cell| cell == 0) {
+                line[self.rng.gen_range(0, width as u32) as usize] = GARBAGE;
+            }
+            if !line.contains(&0) {
+                line[self.rng.gen_range(0, width as u32) as usize] = 0;
+            }
+        } else {
+            let mut hole = self.rng.gen_range(0, width as u32) as usize;
+            if Some(hole) == self.previous_hole {
+                hole = (hole + 1 + self.rng.gen_range(0, width as u32 - 1) as usize) % width;
+            }
+            line[hole] = 0;
+            self.previous_hole = Some(hole);
+        }
+        line
+    }
+}
@@ -0,0 +1,173 @@
+use {read_from_file, write_into_file};
+use mode::{format_time, GameMode, GameResult, Outcome};
+
+const HIGHSCORE_FILE: &str = "scores.txt";
+const NB_HIGHSCORES: usize = 5;
+
+fn slice_to_string(slice: &[u32]) -> String {
+    slice.iter().map(|highscore| highscore.to_string()).collect::<Vec<String>>().join(" ")
+}
+
+fn save_highscores_and_lines(highscores: &[u32], number_of_lines: &[u32]) -> bool {
+    let s_highscores = slice_to_string(highscores);
+    let s_number_of_lines = slice_to_string(number_of_lines);
+    write_into_file(&format!("{}\n{}\n", s_highscores, s_number_of_lines), HIGHSCORE_FILE).is_ok()
+}
+
+fn line_to_slice(line: &str) -> Vec<u32> {
+    line.split(" ").filter_map(|nb| nb.parse::<u32>().ok()).collect()
+}
+
+fn load_highscores_and_lines() -> Option<(Vec<u32>, Vec<u32>)> {
+    if let Ok(content) = read_from_file(HIGHSCORE_FILE) {
+        let mut lines = content.splitn(2, "\n").map(line_to_slice).collect::<Vec<_>>();
+        if lines.len() == 2 {
+            let (lines_sent, highscores) = (lines.pop().unwrap(), lines.pop().unwrap());
+            Some((highscores, lines_sent))
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+fn update_vec(v: &mut Vec<u32>, value: u32) -> bool {
+    if v.len() < NB_HIGHSCORES {
+        v.push(value);
+        true
+    } else {
+        for entry in v.iter_mut() {
+            if value > *entry {
+                *entry = value;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ranking {
+    HighestFirst,
+    LowestFirst,
+}
+
+/// Adds `value` to the table stored in `file_name` and returns its rank if it
+/// was good enough to get in.
+pub fn add_to_table(file_name: &str, value: u32, ranking: Ranking) -> Option<usize> {
+    let mut table = read_from_file(file_name).map(|content| line_to_slice(content.trim()))
+                                             .unwrap_or_default();
+    let rank = table.iter()
+                    .position(|&entry| match ranking {
+                        Ranking::HighestFirst => value > entry,
+                        Ranking::LowestFirst => value < entry,
+                    })
+                    .unwrap_or(table.len());
+    if rank >= NB_HIGHSCORES {
+        return None;
+    }
+    table.insert(rank, value);
+    table.truncate(NB_HIGHSCORES);
+    if write_into_file(&format!("{}\n", slice_to_string(&table)), file_name).is_err() {
+        return None;
+    }
+    Some(rank)
+}
+
+pub fn print_game_information(result: &GameResult) {
+    let mode = result.tetris.mode;
+    let table = if result.ranked { mode.highscore_table() } else { None };
+    let (file_name, ranking) = match table {
+        Some(table) => table,
+        None => return match mode {
+            GameMode::Endless if result.ranked => print_endless_information(result),
+            GameMode::Finesse => print_finesse_information(result),
+            GameMode::Puzzle { .. } => print_puzzle_information(result),
+            // Daily challenges are ranked in their leaderboards, see `daily`.
+            _ => print_unranked_information(result),
+        },
+    };
+    let rank = mode.ranked_value(result)
+                   .and_then(|value| add_to_table(&file_name, value, ranking));
+
+    match result.outcome {
+        Outcome::Won => println!("Well done!"),
+        Outcome::Lost(reason) => println!("Game over ({})...", reason.description()),
+        Outcome::Quit => println!("Game abandoned."),
+    }
+    println!("Mode:            {}", mode.name());
+    let by_time = ranking == Ranking::LowestFirst;
+    let time_rank = if by_time { rank } else { None };
+    let score_rank = if by_time { None } else { rank };
+    println!("Time:            {}{}", format_time(result.time), rank_label(time_rank));
+    println!("Score:           {}{}", result.tetris.score, rank_label(score_rank));
+    println!("Number of lines: {}", result.tetris.nb_lines);
+    println!("Pieces used:     {}", result.tetris.nb_pieces);
+    println!("Current level:   {}", result.tetris.current_level);
+}
+
+fn print_puzzle_information(result: &GameResult) {
+    match result.outcome {
+        Outcome::Won => println!("Puzzle solved!"),
+        Outcome::Lost(reason) => println!("Puzzle failed ({})...", reason.description()),
+        Outcome::Quit => println!("Puzzle abandoned."),
+    }
+    println!("Time:            {}", format_time(result.time));
+    println!("Pieces used:     {}", result.tetris.nb_pieces);
+}
+
+fn print_finesse_information(result: &GameResult) {
+    println!("Training over.");
+    println!("Time:            {}", format_time(result.time));
+    println!("Pieces used:     {}", result.tetris.nb_pieces);
+}
+
+fn print_unranked_information(result: &GameResult) {
+    match result.outcome {
+        Outcome::Won => println!("Well done!"),
+        Outcome::Lost(reason) => println!("Game over ({})...", reason.description()),
+        Outcome::Quit => println!("Game abandoned."),
+    }
+    println!("Time:            {}", format_time(result.time));
+    println!("Score:           {}", result.tetris.score);
+    println!("Number of lines: {}", result.tetris.nb_lines);
+    println!("Pieces used:     {}", result.tetris.nb_pieces);
+    if !result.ranked {
+        println!("Not ranked: the game speed or delays aren't the default ones.");
+    }
+}
+
+fn rank_label(rank: Option<usize>) -> String {
+    match rank {
+        Some(0) => " [NEW HIGHSCORE]".to_string(),
+        Some(rank) => format!(" [#{} IN HIGHSCORES]", rank + 1),
+        None => String::new(),
+    }
+}
+
+fn print_endless_information(result: &GameResult) {
+    let tetris = &result.tetris;
+    let mut new_highest_highscore = true;
+    let mut new_highest_lines_sent = true;
+    if let Some((mut highscores, mut lines_sent)) = load_highscores_and_lines() {
+        new_highest_highscore = update_vec(&mut highscores, tetris.score);
+        new_highest_lines_sent = update_vec(&mut lines_sent, tetris.nb_lines);
+        if new_highest_highscore || new_highest_lines_sent {
+            save_highscores_and_lines(&highscores, &lines_sent);
+        }
+    } else {
+        save_highscores_and_lines(&[tetris.score], &[tetris.nb_lines]);
+    }
+    match result.outcome {
+        Outcome::Lost(reason) => println!("Game over ({})...", reason.description()),
+        _ => println!("Game over..."),
+    }
+    println!("Score:           {}{}",
+             tetris.score,
+             if new_highest_highscore { " [NEW HIGHSCORE]"} else { "" });
+    println!("Number of lines: {}{}",
+             tetris.nb_lines,
+             if new_highest_lines_sent { " [NEW HIGHSCORE]"} else { "" });
+    println!("Current level:   {}", tetris.current_level);
+}
@@ -0,0 +1,85 @@
+//! The Tetris engine, without any frontend.
+//!
+//! A `Tetris` holds the rules and the state of a game; a `Game` wraps it with the
+//! timers which make pieces fall and spawn, and is advanced by whole ticks of
+//! `TICK` (1/60th of a second) with the actions of the player for that tick:
+//!
+//! ```no_run
+//! extern crate tetris;
+//!
+//! use tetris::{Action, Game, GameMode, PieceSet, Tetris};
+//!
+//! fn main() {
+//!     let mut game = Game::new(Tetris::with_seed(42, GameMode::Sprint, PieceSet::standard()));
+//!     let mut outcome = None;
+//!     while outcome.is_none() {
+//!         outcome = game.tick(&[Action::HardDrop], None);
+//!     }
+//!     for line in game.tetris.game_map.lines() {
+//!         println!("{:?}", line);
+//!     }
+//!     println!("{:?} with {} lines", outcome, game.tetris.nb_lines);
+//! }
+//! ```
+//!
+//! Cells of the map are 0 when empty, the id of the piece they come from plus one,
+//! or `garbage::GARBAGE`. The same game always plays the same way from the same
+//! seed and actions, which is what replays rely on. A `GameObserver` added with
+//! `Tetris::add_observer` gets told about the pieces, clears and levels.
+//!
+//! With the `capi` feature, the `capi` module exports the engine to C, see
+//! "include/tetris.h". With the `scripting` feature, the `script` module plays
+//! the game modes written as Rhai scripts in the "mods" directory.
+
+extern crate rand;
+#[cfg(feature = "scripting")]
+extern crate rhai;
+
+pub mod accessibility;
+pub mod ai;
+pub mod animation;
+pub mod board;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod classic;
+pub mod daily;
+pub mod finesse;
+pub mod game;
+pub mod garbage;
+pub mod highscores;
+pub mod mode;
+pub mod observer;
+pub mod pieces;
+pub mod puzzle;
+pub mod randomizer;
+pub mod replay;
+pub mod save;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod settings;
+pub mod stats;
+pub mod tetrimino;
+pub mod tetris;
+pub mod trainer;
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+pub use board::Board;
+pub use game::{Game, Timing, TICK};
+pub use mode::{GameMode, GameOver, GameResult, Outcome};
+pub use observer::GameObserver;
+pub use pieces::PieceSet;
+pub use tetris::{Action, Event, Tetris};
+
+pub fn write_into_file(content: &str, file_name: &str) -> io::Result<()> {
+    let mut f = File::create(file_name)?;
+    f.write_all(content.as_bytes())
+}
+
+pub fn read_from_file(file_name: &str) -> io::Result<String> {
+    let mut f = File::open(file_name)?;
+    let mut content = String::new();
+    f.read_to_string(&mut content)?;
+    Ok(content)
+}
@@ -1,551 +1,713 @@
-extern crate rand;
+#[cfg(feature = "sdl")]
 extern crate sdl2;
-
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::{Canvas, Texture, TextureCreator};
-use sdl2::video::{Window, WindowContext};
-
-use std::fs::File;
-use std::io::{self, Read, Write};
+extern crate tetris;
+
+#[cfg(not(any(feature = "sdl", feature = "tui")))]
+compile_error!("at least one of the `sdl` and `tui` features has to be enabled");
+
+#[cfg(feature = "sdl")]
+mod encoder;
+#[cfg(feature = "sdl")]
+mod font;
+#[cfg(feature = "sdl")]
+mod sdl_frontend;
+mod spectator;
+#[cfg(feature = "sdl")]
+mod theme;
+#[cfg(feature = "tui")]
+mod tui;
+
+use std::env;
+use std::io::{self, Write};
 use std::thread::sleep;
-use std::time::{Duration, SystemTime};
-
-const TETRIS_HEIGHT: usize = 40;
-const HIGHSCORE_FILE: &'static str = "scores.txt";
-const LEVEL_TIMES: [u32; 10] = [1000, 850, 700, 600, 500, 400, 300, 250, 221, 190];
-const LEVEL_LINES: [u32; 10] = [20,   40,  60,  80,  100, 120, 140, 160, 180, 200];
-const NB_HIGHSCORES: usize = 5;
-
-type Piece = Vec<Vec<u8>>;
-type States = Vec<Piece>;
+use std::time::{Duration, Instant};
+
+use tetris::ai::Weights;
+use tetris::animation::Animations;
+use tetris::classic::MAX_START_LEVEL;
+use tetris::daily::{self, Date};
+use tetris::highscores::print_game_information;
+use tetris::mode::MAX_DIG_LINES;
+use tetris::puzzle::{self, Puzzle};
+use tetris::replay::Recorder;
+use tetris::settings::Settings;
+use tetris::{save, stats, trainer};
+use tetris::{Action, Game, GameMode, GameResult, Outcome, PieceSet, Tetris, Timing, TICK};
+
+use spectator::{Server, Viewer};
+
+/// Ticks simulated at most in a single frame.
+const MAX_CATCH_UP_TICKS: u32 = 5;
+
+const USAGE: &str = "\
+Usage: tetris [--tui] [--fps N] [--speed PERCENT] [--stats JSON_FILE] [--record REPLAY_FILE]
+              [--mode MODE] [--pieces PIECES_FILE] [--bot WEIGHTS_FILE] [--spectate ADDRESS]
+              [--profile NAME]
+       tetris [--tui] [--fps N] [--stats JSON_FILE] [--record REPLAY_FILE] --puzzle PUZZLE_FILE
+       tetris [--tui] [--fps N] [--stats JSON_FILE] [--record REPLAY_FILE] puzzles
+       tetris [--tui] [--fps N] [--stats JSON_FILE] [--pieces PIECES_FILE] --mod NAME
+       tetris [--tui] watch ADDRESS
+       tetris leaderboard [DATE]
+       tetris export REPLAY_FILE OUTPUT_FILE [--fps N] [--scale SCALE]
+       tetris train [OPTIONS]
+
+Modes: endless (default), sprint, ultra, marathon, dig, finesse, classic and daily. The
+dig mode also takes:
+    --garbage LINES   number of garbage lines to clear (default: 10)
+    --messy           garbage lines have several holes
+    --rising SECONDS  a new garbage line comes up every SECONDS seconds
+The finesse mode points out the pieces placed with more inputs than needed, a
+direction held until the piece stops (DAS) counting as a single input.
+The classic mode plays like the NES version: its pieces, rotations without kicks,
+randomizer, speeds and scores, and no hold or hard drop. It also takes:
+    --level N         level to start from, 0 to 19 (default: 0)
+The daily mode is the challenge of the day: the pieces are the same for everyone
+on a given date (in UTC) and the game is played like the ultra mode. Only the
+first attempt of each profile every day is ranked, and it can't be saved.
+--profile picks the name under which it's ranked, it overrides the \"profile\"
+setting. `leaderboard` shows the ranking of the challenge of DATE (YYYY-MM-DD,
+default: today) and the winners of the days before.
+
+The pieces can be replaced by the ones of a definition file, such as those of the
+\"pieces\" directory, except in the classic and daily modes.
+
+--stats appends the statistics of the game to JSON_FILE, as a JSON object on a
+single line.
+
+--fps caps the frame rate (0 for no cap), it overrides the \"fps_cap\" setting of
+\"settings.txt\". --speed changes the speed of the falling pieces (from 10 to 200
+percent), it overrides the \"game_speed\" setting. Games played with another speed,
+or other delays than the default ones, don't enter the high scores.
+
+--record writes the replay of the game to REPLAY_FILE. `export` turns it into an
+animated GIF, or an animated PNG if OUTPUT_FILE doesn't end with \".gif\", with N
+frames per second (default: 30) resized by SCALE (default: 1). It needs the SDL
+version, but doesn't open any window.
+
+--spectate streams the game to the viewers connecting to ADDRESS (such as
+127.0.0.1:7878, or 0.0.0.0:7878 to accept other machines), which watch it with
+`tetris watch ADDRESS`.
+
+In the SDL version, O opens the options menu to pick a theme from the \"themes\"
+directory, a colour scheme for colour-blind players, letters on the cells and a
+high-contrast look, and P saves a screenshot. The window can be resized, F11
+switches to full screen.
+
+Quitting a game saves it, it can then be continued by starting the game again
+without choosing a mode.
+
+`puzzles` lets you pick a puzzle from the packs of the \"puzzles\" directory.
+
+--mod plays the game mode written in the script \"mods/NAME.rhai\" (it needs the
+`scripting` feature). Mods aren't saved when quitting and can't be recorded or
+streamed with --spectate.";
+
+/// Everything the command line can change about a game.
+struct GameOptions {
+    mode: GameMode,
+    piece_set: PieceSet,
+    bot: Option<Weights>,
+    puzzle: Option<Puzzle>,
+    saved: Option<Game>,
+    /// Game of the mod chosen with `--mod`, set up by its script.
+    modded: Option<Game>,
+    settings: Settings,
+    /// File to write the replay of the game to.
+    record: Option<String>,
+    /// Server streaming the game to spectators.
+    spectate: Option<Server>,
+    /// Profile taking its ranked attempt at the daily challenge with the game.
+    daily_profile: Option<String>,
+}
 
-trait TetriminoGenerator {
-    fn new() -> Tetrimino;
+/// Everything a frontend needs to draw the game.
+struct Frame<'a> {
+    tetris: &'a Tetris,
+    /// The information the game mode wants displayed.
+    hud: &'a [(&'static str, String)],
+    /// How far (from 0 to 1) the current piece is on its way to the next line.
+    #[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+    interpolation: f64,
+    /// Id of the next piece, when it's known.
+    #[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+    preview: Option<u8>,
+    animations: &'a Animations,
 }
 
-struct TetriminoI;
-
-impl TetriminoGenerator for TetriminoI {
-    fn new() -> Tetrimino {
-        Tetrimino {
-            states: vec![vec![vec![1, 1, 1, 1],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                         vec![vec![0, 1, 0, 0],
-                              vec![0, 1, 0, 0],
-                              vec![0, 1, 0, 0],
-                              vec![0, 1, 0, 0]]],
-            x: 4,
-            y: 0,
-            current_state: 0,
-        }
+/// What the SDL and the terminal versions of the game have to provide to `play`.
+trait Frontend {
+    /// Returns the actions requested by the player since the last call, or `None`
+    /// if they want to quit.
+    fn poll_actions(&mut self) -> Option<Vec<Action>>;
+    /// Returns the rotation and the hold if their keys are held down, for the
+    /// IRS/IHS. Terminals don't tell when keys get released, so they can only
+    /// have the ones pressed while waiting for the next piece.
+    fn held_actions(&self) -> Vec<Action> {
+        Vec::new()
+    }
+    fn draw(&mut self, frame: &Frame);
+    /// Whether the game is paused, for example while a menu is open.
+    fn paused(&self) -> bool {
+        false
     }
 }
 
-struct TetriminoJ;
-
-impl TetriminoGenerator for TetriminoJ {
-    fn new() -> Tetrimino {
-        Tetrimino {
-            states: vec![vec![vec![2, 2, 2, 0],
-                              vec![2, 0, 0, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                         vec![vec![2, 2, 0, 0],
-                              vec![0, 2, 0, 0],
-                              vec![0, 2, 0, 0],
-                              vec![0, 0, 0, 0]],
-                         vec![vec![0, 0, 2, 0],
-                              vec![2, 2, 2, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                         vec![vec![2, 0, 0, 0],
-                              vec![2, 0, 0, 0],
-                              vec![2, 2, 0, 0],
-                              vec![0, 0, 0, 0]]],
-            x: 4,
-            y: 0,
-            current_state: 0,
+/// Runs a game until it's over and returns its final state.
+fn play<F: Frontend>(frontend: &mut F, mut options: GameOptions) -> GameResult {
+    let mut game = match (options.saved.or(options.modded), options.puzzle.as_ref()) {
+        (Some(game), _) => game,
+        (None, Some(puzzle)) => Game::new(puzzle.start()),
+        (None, None) => Game::new(Tetris::new(options.mode, options.piece_set)),
+    };
+    let frame_time = match options.settings.fps_cap {
+        0 => None,
+        fps => Some(Duration::from_secs(1) / fps),
+    };
+    game.set_timing(options.settings.timing);
+    let mut animations = Animations::new(options.settings.animations,
+                                         options.settings.clear_animation,
+                                         options.settings.screen_shake);
+    // The bot's moves aren't actions, so its games can't be replayed, and the
+    // scripts of the mods aren't part of the replays.
+    let mut recorder = match options.record {
+        Some(_) if options.bot.is_none() && game.tetris.mode != GameMode::Mod => Some(Recorder::new(&game)),
+        _ => None,
+    };
+    // The attempt is entered as soon as the game starts, so that quitting
+    // doesn't give another one.
+    let attempt = match (game.tetris.mode, options.daily_profile.as_ref()) {
+        (GameMode::Daily { date }, Some(profile)) => Some(daily::save_attempt(date, profile, 0)),
+        _ => None,
+    };
+    let mut pending = Vec::new();
+    let mut previous = Instant::now();
+    // Time the simulation is late on the real time.
+    let mut lag = Duration::new(0, 0);
+
+    let outcome = 'game: loop {
+        let frame_start = Instant::now();
+        let elapsed = frame_start - previous;
+        lag += elapsed;
+        previous = frame_start;
+        match frontend.poll_actions() {
+            Some(actions) => pending.extend(actions),
+            None => break Outcome::Quit,
         }
+        // After a stall, the missed time is dropped instead of being simulated:
+        // catching up on all of it could take longer than the stall itself.
+        lag = lag.min(TICK * MAX_CATCH_UP_TICKS);
+        let held = if frontend.paused() {
+            lag = Duration::new(0, 0);
+            pending.clear();
+            Vec::new()
+        } else {
+            frontend.held_actions()
+        };
+        game.set_held(&held);
+        while lag >= TICK {
+            lag -= TICK;
+            if let Some(ref mut recorder) = recorder {
+                recorder.tick(&pending, &held);
+            }
+            if let Some(outcome) = game.tick(&pending, options.bot.as_ref()) {
+                break 'game outcome
+            }
+            pending.clear();
+        }
+        let events = game.take_events();
+        for event in &events {
+            animations.handle(event);
+        }
+        if let Some(ref mut server) = options.spectate {
+            server.broadcast(&game, &events);
+        }
+        animations.update(elapsed, game.clear_progress());
+        frontend.draw(&Frame {
+            tetris: &game.tetris,
+            hud: &game.hud(),
+            interpolation: if options.settings.interpolation { game.fall_progress() } else { 0. },
+            preview: game.tetris.next_preview(),
+            animations: &animations,
+        });
+
+        if let Some(frame_time) = frame_time {
+            let elapsed = frame_start.elapsed();
+            if elapsed < frame_time {
+                sleep(frame_time - elapsed);
+            }
+        }
+    };
+    if let Some(ref mut server) = options.spectate {
+        server.finish(outcome);
     }
-}
-
-struct TetriminoL;
-
-impl TetriminoGenerator for TetriminoL {
-    fn new() -> Tetrimino {
-        Tetrimino {
-            states: vec![vec![vec![3, 3, 3, 0],
-                              vec![0, 0, 3, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                         vec![vec![0, 3, 0, 0],
-                              vec![0, 3, 0, 0],
-                              vec![3, 3, 0, 0],
-                              vec![0, 0, 0, 0]],
-                         vec![vec![3, 0, 0, 0],
-                              vec![3, 3, 3, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                         vec![vec![3, 3, 0, 0],
-                              vec![3, 0, 0, 0],
-                              vec![3, 0, 0, 0],
-                              vec![0, 0, 0, 0]]],
-            x: 4,
-            y: 0,
-            current_state: 0,
+    if let Some(Err(e)) = attempt {
+        println!("Failed to save the daily attempt: {}", e);
+    }
+    if let (Some(recorder), Some(file_name)) = (recorder, options.record) {
+        if let Err(e) = recorder.save(&file_name) {
+            println!("Failed to save the replay: {}", e);
         }
     }
-}
-
-struct TetriminoO;
-
-impl TetriminoGenerator for TetriminoO {
-    fn new() -> Tetrimino {
-        Tetrimino {
-            states: vec![vec![vec![4, 4, 0, 0],
-                              vec![4, 4, 0, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]]],
-            x: 5,
-            y: 0,
-            current_state: 0,
+    // Puzzles and bot games aren't worth continuing, and some modes can't be.
+    if outcome == Outcome::Quit && options.puzzle.is_none() && options.bot.is_none() &&
+       game.tetris.mode.can_be_continued() {
+        if let Err(e) = save::save(&game) {
+            println!("Failed to save the game: {}", e);
         }
     }
+    GameResult {
+        tetris: game.tetris,
+        time: game.time,
+        outcome,
+        ranked: game.ranked,
+    }
 }
 
-struct TetriminoS;
-
-impl TetriminoGenerator for TetriminoS {
-    fn new() -> Tetrimino {
-        Tetrimino {
-            states: vec![vec![vec![0, 5, 5, 0],
-                              vec![5, 5, 0, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                         vec![vec![0, 5, 0, 0],
-                              vec![0, 5, 5, 0],
-                              vec![0, 0, 5, 0],
-                              vec![0, 0, 0, 0]]],
-            x: 4,
-            y: 0,
-            current_state: 0,
+/// Shows the game streamed by a spectator server until the player quits.
+fn watch<F: Frontend>(frontend: &mut F, mut viewer: Viewer, settings: &Settings) -> Result<(), String> {
+    let mut animations = Animations::new(settings.animations, settings.clear_animation,
+                                         settings.screen_shake);
+    let frame_time = Duration::from_secs(1) / settings.fps_cap.max(1);
+    let mut previous = Instant::now();
+    let mut connected = true;
+    while frontend.poll_actions().is_some() {
+        let frame_start = Instant::now();
+        let elapsed = frame_start - previous;
+        previous = frame_start;
+        connected = connected && viewer.update()?;
+        for event in viewer.take_events() {
+            animations.handle(&event);
+        }
+        animations.update(elapsed, viewer.clear_progress);
+        if let Some(ref game) = viewer.game {
+            let mut hud = game.hud();
+            match viewer.over {
+                Some(ref over) => hud.push(("Game over", over.clone())),
+                None if !connected => hud.push(("Stream", "disconnected".to_string())),
+                None => {}
+            }
+            frontend.draw(&Frame {
+                tetris: &game.tetris,
+                hud: &hud,
+                interpolation: 0.,
+                preview: None,
+                animations: &animations,
+            });
+        } else if !connected {
+            return Err("the server closed the connection".to_string());
+        }
+        let elapsed = frame_start.elapsed();
+        if elapsed < frame_time {
+            sleep(frame_time - elapsed);
         }
     }
+    Ok(())
 }
 
-struct TetriminoZ;
-
-impl TetriminoGenerator for TetriminoZ {
-    fn new() -> Tetrimino {
-        Tetrimino {
-            states: vec![vec![vec![6, 6, 0, 0],
-                              vec![0, 6, 6, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                         vec![vec![0, 0, 6, 0],
-                              vec![0, 6, 6, 0],
-                              vec![0, 6, 0, 0],
-                              vec![0, 0, 0, 0]]],
-            x: 4,
-            y: 0,
-            current_state: 0,
+/// Asks the player whether they want to continue their saved game.
+fn offer_saved_game() -> Option<Game> {
+    if !save::exists() {
+        return None;
+    }
+    print!("A saved game was found. Continue it? [Y/n] ");
+    io::stdout().flush().ok()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).ok()?;
+    if answer.trim().eq_ignore_ascii_case("n") {
+        return None;
+    }
+    match save::load() {
+        Ok(saved) => Some(saved),
+        Err(e) => {
+            println!("Couldn't continue the saved game: {}", e);
+            match save::discard() {
+                Ok(backup) => println!("It was moved to \"{}\", starting a new game.", backup),
+                Err(e) => println!("Failed to move it away: {}", e),
+            }
+            None
         }
     }
 }
 
-struct TetriminoT;
-
-impl TetriminoGenerator for TetriminoT {
-    fn new() -> Tetrimino {
-        Tetrimino {
-            states: vec![vec![vec![7, 7, 7, 0],
-                              vec![0, 7, 0, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                         vec![vec![0, 7, 0, 0],
-                              vec![7, 7, 0, 0],
-                              vec![0, 7, 0, 0],
-                              vec![0, 0, 0, 0]],
-                         vec![vec![0, 7, 0, 0],
-                              vec![7, 7, 7, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                         vec![vec![0, 7, 0, 0],
-                              vec![0, 7, 7, 0],
-                              vec![0, 7, 0, 0],
-                              vec![0, 0, 0, 0]]],
-            x: 4,
-            y: 0,
-            current_state: 0,
-        }
+#[cfg(feature = "sdl")]
+fn start(use_tui: bool, options: GameOptions) -> Option<GameResult> {
+    if use_tui {
+        return start_tui(options);
     }
+    Some(sdl_frontend::run(options))
 }
 
-struct Tetrimino {
-    states: States,
-    x: isize,
-    y: usize,
-    current_state: u8,
+#[cfg(not(feature = "sdl"))]
+fn start(_use_tui: bool, options: GameOptions) -> Option<GameResult> {
+    start_tui(options)
 }
 
-impl Tetrimino {
-    fn rotate(&mut self, game_map: &[Vec<u8>]) {
-        let mut tmp_state = self.current_state + 1;
-        if tmp_state as usize >= self.states.len() {
-            tmp_state = 0;
-        }
-        let x_pos = [0, -1, 1, -2, 2, -3];
-        for x in x_pos.iter() {
-            if self.test_position(game_map, tmp_state as usize,
-                                  self.x + x, self.y) == true {
-                self.current_state = tmp_state;
-                self.x += *x;
-                break
-            }
-        }
+#[cfg(feature = "sdl")]
+fn export(args: &[String]) {
+    if args.len() < 2 {
+        println!("{}", USAGE);
+        return
     }
-
-    fn test_position(&self, game_map: &[Vec<u8>],
-                     tmp_state: usize, x: isize, y: usize) -> bool {
-        for shift_y in 0..4 {
-            for shift_x in 0..4 {
-                let x = x + shift_x;
-                if self.states[tmp_state][shift_y][shift_x as usize] != 0 &&
-                    (y + shift_y >= game_map.len() ||
-                     x < 0 ||
-                     x as usize >= game_map[y + shift_y].len() ||
-                     game_map[y + shift_y][x as usize] != 0) {
-                    return false;
+    let (mut fps, mut scale) = (30, 1.);
+    let mut i = 2;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--fps" if i + 1 < args.len() => {
+                i += 1;
+                match args[i].parse::<u32>() {
+                    Ok(value) if value > 0 => fps = value,
+                    _ => {
+                        println!("Invalid value for --fps: \"{}\"", args[i]);
+                        return
+                    }
+                }
+            }
+            "--scale" if i + 1 < args.len() => {
+                i += 1;
+                match args[i].parse::<f64>() {
+                    Ok(value) if value > 0. && value <= 4. => scale = value,
+                    _ => {
+                        println!("Invalid value for --scale: \"{}\"", args[i]);
+                        return
+                    }
                 }
             }
+            arg => {
+                println!("Unknown argument \"{}\"", arg);
+                return
+            }
         }
-        return true;
+        i += 1;
     }
-
-    fn test_current_position(&self, game_map: &[Vec<u8>]) -> bool {
-        self.test_position(game_map, self.current_state as usize, self.x, self.y)
+    match sdl_frontend::export(&args[0], &args[1], fps, scale, &Settings::load()) {
+        Ok(()) => println!("Replay exported to \"{}\"", args[1]),
+        Err(e) => println!("Failed to export the replay: {}", e),
     }
+}
 
-    fn change_position(&mut self, game_map: &[Vec<u8>], new_x: isize, new_y: usize) -> bool {
-        if self.test_position(game_map, self.current_state as usize, new_x, new_y) == true {
-            self.x = new_x as isize;
-            self.y = new_y;
-            true
-        } else {
-            false
+#[cfg(not(feature = "sdl"))]
+fn export(_args: &[String]) {
+    println!("Exporting replays needs the SDL version (`sdl` feature).");
+}
+
+#[cfg(feature = "tui")]
+fn start_tui(options: GameOptions) -> Option<GameResult> {
+    match tui::run(options) {
+        Ok(result) => Some(result),
+        Err(e) => {
+            println!("Failed to start the game in the terminal: {}", e);
+            None
         }
     }
 }
 
-struct Tetris {
-    game_map: Vec<Vec<u8>>,
-    current_level: u32,
-    score: u32,
-    nb_lines: u32,
-    current_piece: Option<Tetrimino>,
+#[cfg(not(feature = "tui"))]
+fn start_tui(_options: GameOptions) -> Option<GameResult> {
+    println!("This build doesn't include the terminal version (`tui` feature).");
+    None
 }
 
-impl Tetris {
-    fn new() -> Tetris {
-        let mut game_map = Vec::new();
-        for _ in 0..16 {
-            game_map.push(vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
-        }
-        Tetris {
-            game_map: game_map,
-            current_level: 1,
-            score: 0,
-            nb_lines: 0,
-            current_piece: None,
+#[cfg(feature = "scripting")]
+fn start_mod(name: &str, piece_set: PieceSet) -> Result<Game, String> {
+    use tetris::script::Script;
+
+    match Script::load(name) {
+        Ok(script) => Ok(script.start(piece_set)),
+        Err(e) => {
+            let mods = Script::list();
+            if mods.is_empty() {
+                Err(e)
+            } else {
+                Err(format!("{} (available mods: {})", e, mods.join(", ")))
+            }
         }
     }
+}
 
-    fn update_score(&mut self, to_add: u32) {
-        self.score += to_add;
-    }
+#[cfg(not(feature = "scripting"))]
+fn start_mod(_name: &str, _piece_set: PieceSet) -> Result<Game, String> {
+    Err("this build doesn't include mods (`scripting` feature)".to_string())
+}
 
-    fn increase_level(&mut self) {
-        self.current_level += 1;
+#[cfg(feature = "sdl")]
+fn start_watch(use_tui: bool, viewer: Viewer, settings: &Settings) -> Result<(), String> {
+    if use_tui {
+        return start_tui_watch(viewer, settings);
     }
+    sdl_frontend::watch(viewer, settings)
+}
 
-    fn increase_line(&mut self) {
-        self.nb_lines += 1;
-        if self.nb_lines > LEVEL_LINES[self.current_level as usize - 1] {
-            self.increase_level();
-        }
-    }
+#[cfg(not(feature = "sdl"))]
+fn start_watch(_use_tui: bool, viewer: Viewer, settings: &Settings) -> Result<(), String> {
+    start_tui_watch(viewer, settings)
+}
 
-    fn check_lines(&mut self) {
-        let mut y = 0;
-        let mut score_add = 0;
+#[cfg(feature = "tui")]
+fn start_tui_watch(viewer: Viewer, settings: &Settings) -> Result<(), String> {
+    tui::watch(viewer, settings)
+}
 
-        while y < self.game_map.len() {
-            let mut complete = true;
+#[cfg(not(feature = "tui"))]
+fn start_tui_watch(_viewer: Viewer, _settings: &Settings) -> Result<(), String> {
+    Err("this build doesn't include the terminal version (`tui` feature)".to_string())
+}
 
-            for x in &self.game_map[y] {
-                if *x == 0 {
-                    complete = false;
-                    break
-                }
-            }
-            if complete == true {
-                score_add += self.current_level;
-                self.game_map.remove(y);
-                y -= 1;
-            }
-            y += 1;
-        }
-        if self.game_map.len() == 0 {
-            // A "tetris"!
-            score_add += 1000;
-        }
-        self.update_score(score_add);
-        while self.game_map.len() < 16 {
-            self.increase_line();
-            self.game_map.insert(0, vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
-        }
+fn main() {
+    let args = env::args().collect::<Vec<_>>();
+    if args.len() > 1 && args[1] == "train" {
+        trainer::run(&args[2..]);
+        return
     }
-
-    fn create_new_tetrimino(&self) -> Tetrimino {
-        static mut PREV: u8 = 7;
-        let mut rand_nb = rand::random::<u8>() % 7;
-        if unsafe { PREV } == rand_nb {
-            rand_nb = rand::random::<u8>() % 7;
-        }
-        unsafe { PREV = rand_nb; }
-        match rand_nb {
-            0 => TetriminoI::new(),
-            1 => TetriminoJ::new(),
-            2 => TetriminoL::new(),
-            3 => TetriminoO::new(),
-            4 => TetriminoS::new(),
-            5 => TetriminoZ::new(),
-            6 => TetriminoT::new(),
-            _ => unreachable!(),
+    if args.len() > 1 && args[1] == "export" {
+        export(&args[2..]);
+        return
+    }
+    if args.len() > 1 && args[1] == "leaderboard" {
+        match args.get(2) {
+            Some(date) => match Date::parse(date) {
+                Some(date) => daily::print_leaderboards(date, None),
+                None => println!("Invalid date \"{}\", expected YYYY-MM-DD", date),
+            },
+            None => daily::print_leaderboards(Date::today(), Some(&Settings::load().profile)),
         }
+        return
     }
-
-    fn make_permanent(&mut self) {
-        let mut to_add = 0;
-        if let Some(ref mut piece) = self.current_piece {
-            let mut shift_y = 0;
-
-            while shift_y < piece.states[piece.current_state as usize].len() &&
-                  piece.y + shift_y < self.game_map.len() {
-                let mut shift_x = 0;
-
-                while shift_x < piece.states[piece.current_state as usize][shift_y].len() &&
-                      (piece.x + shift_x as isize) < self.game_map[piece.y + shift_y].len() as isize {
-                    if piece.states[piece.current_state as usize][shift_y][shift_x] != 0 {
-                        let x = piece.x + shift_x as isize;
-                        self.game_map[piece.y + shift_y][x as usize] =
-                            piece.states[piece.current_state as usize][shift_y][shift_x];
+    let mut bot = None;
+    let mut mode = GameMode::Endless;
+    let mut piece_set = PieceSet::standard();
+    let mut settings = Settings::load();
+    let (mut garbage, mut rising, mut messy) = (None, None, false);
+    let mut start_level = None;
+    let mut puzzle = None;
+    let mut stats_file = None;
+    let mut record = None;
+    let mut spectate = None;
+    let mut mod_name = None;
+    // A saved game is only offered if the player didn't ask for a specific game.
+    let mut new_game = false;
+    let mut use_tui = false;
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--tui" => use_tui = true,
+            "--mode" if i + 1 < args.len() => {
+                i += 1;
+                new_game = true;
+                // The engine doesn't read the clock, the date of the daily
+                // challenge is picked here.
+                let named = match args[i].as_str() {
+                    "daily" => Some(GameMode::Daily { date: Date::today() }),
+                    name => GameMode::from_name(name),
+                };
+                match named {
+                    Some(m) => mode = m,
+                    None => {
+                        println!("Unknown mode \"{}\", available modes are: endless, sprint, \
+                                  ultra, marathon, dig, finesse, classic and daily", args[i]);
+                        return
                     }
-                    shift_x += 1;
                 }
-                shift_y += 1;
             }
-            to_add += self.current_level;
-        }
-        self.update_score(to_add);
-        self.check_lines();
-        self.current_piece = None;
-    }
-}
-
-fn handle_events(tetris: &mut Tetris, quit: &mut bool, timer: &mut SystemTime,
-                 event_pump: &mut sdl2::EventPump) -> bool {
-    let mut make_permanent = false;
-    if let Some(ref mut piece) = tetris.current_piece {
-        let mut tmp_x = piece.x;
-        let mut tmp_y = piece.y;
-
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. } |
-                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                    *quit = true;
-                    break
+            "--fps" | "--speed" | "--profile" if i + 1 < args.len() => {
+                let key = match args[i].as_str() {
+                    "--fps" => "fps_cap",
+                    "--speed" => "game_speed",
+                    _ => "profile",
+                };
+                i += 1;
+                if let Err(e) = settings.set(key, &args[i]) {
+                    println!("{}", e);
+                    return
                 }
-                Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
-                    *timer = SystemTime::now();
-                    tmp_y += 1;
+            }
+            "--stats" if i + 1 < args.len() => {
+                i += 1;
+                stats_file = Some(args[i].clone());
+            }
+            "--record" if i + 1 < args.len() => {
+                i += 1;
+                record = Some(args[i].clone());
+            }
+            "--spectate" if i + 1 < args.len() => {
+                i += 1;
+                match Server::bind(&args[i]) {
+                    Ok(server) => spectate = Some(server),
+                    Err(e) => {
+                        println!("Failed to listen on \"{}\": {}", args[i], e);
+                        return
+                    }
                 }
-                Event::KeyDown { keycode: Some(Keycode::Right), .. } => {
-                    tmp_x += 1;
+            }
+            "watch" if i + 1 < args.len() => {
+                i += 1;
+                let viewer = match Viewer::connect(&args[i]) {
+                    Ok(viewer) => viewer,
+                    Err(e) => {
+                        println!("Failed to connect to \"{}\": {}", args[i], e);
+                        return
+                    }
+                };
+                if let Err(e) = start_watch(use_tui, viewer, &settings) {
+                    println!("Failed to watch the game: {}", e);
                 }
-                Event::KeyDown { keycode: Some(Keycode::Left), .. } => {
-                    tmp_x -= 1;
+                return
+            }
+            "--mod" if i + 1 < args.len() => {
+                i += 1;
+                new_game = true;
+                mod_name = Some(args[i].clone());
+            }
+            "--pieces" if i + 1 < args.len() => {
+                i += 1;
+                new_game = true;
+                match PieceSet::load(&args[i]) {
+                    Ok(set) => piece_set = set,
+                    Err(e) => {
+                        println!("Failed to load pieces: {}", e);
+                        return
+                    }
                 }
-                Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
-                    piece.rotate(&tetris.game_map);
+            }
+            "--bot" if i + 1 < args.len() => {
+                i += 1;
+                new_game = true;
+                match Weights::load(&args[i]) {
+                    Ok(weights) => bot = Some(weights),
+                    Err(e) => {
+                        println!("Failed to load bot weights from \"{}\": {}", args[i], e);
+                        return
+                    }
                 }
-                Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
-                    let x = piece.x;
-                    let mut y = piece.y;
-                    while piece.change_position(&tetris.game_map, x, y + 1) == true {
-                        y += 1;
+            }
+            "--garbage" | "--rising" if i + 1 < args.len() => {
+                let option = args[i].clone();
+                i += 1;
+                let value = match args[i].parse::<u32>() {
+                    Ok(value) => value,
+                    Err(_) => {
+                        println!("Invalid value for {}: \"{}\"", option, args[i]);
+                        return
                     }
-                    make_permanent = true;
+                };
+                if option == "--garbage" {
+                    garbage = Some(value.clamp(1, MAX_DIG_LINES));
+                } else {
+                    rising = Some(value);
                 }
-                _ => {}
             }
-        }
-        if !make_permanent {
-            if piece.change_position(&tetris.game_map, tmp_x, tmp_y) == false &&
-               tmp_y != piece.y {
-                make_permanent = true;
+            "--messy" => messy = true,
+            "--level" if i + 1 < args.len() => {
+                i += 1;
+                match args[i].parse::<u32>() {
+                    Ok(level) if level <= MAX_START_LEVEL => start_level = Some(level),
+                    _ => {
+                        println!("Invalid level \"{}\", it goes from 0 to {}", args[i], MAX_START_LEVEL);
+                        return
+                    }
+                }
             }
-        }
-    }
-    if make_permanent {
-        tetris.make_permanent();
-        *timer = SystemTime::now();
-    }
-    make_permanent
-}
-
-fn write_into_file(content: &str, file_name: &str) -> io::Result<()> {
-    let mut f = File::create(file_name)?;
-    f.write_all(content.as_bytes())
-}
-
-fn read_from_file(file_name: &str) -> io::Result<String> {
-    let mut f = File::open(file_name)?;
-    let mut content = String::new();
-    f.read_to_string(&mut content)?;
-    Ok(content)
-}
-
-fn slice_to_string(slice: &[u32]) -> String {
-    slice.iter().map(|highscore| highscore.to_string()).collect::<Vec<String>>().join(" ")
-}
-
-fn save_highscores_and_lines(highscores: &[u32], number_of_lines: &[u32]) -> bool {
-    let s_highscores = slice_to_string(highscores);
-    let s_number_of_lines = slice_to_string(number_of_lines);
-    write_into_file(&format!("{}\n{}\n", s_highscores, s_number_of_lines), HIGHSCORE_FILE).is_ok()
-}
-
-fn line_to_slice(line: &str) -> Vec<u32> {
-    line.split(" ").filter_map(|nb| nb.parse::<u32>().ok()).collect()
-}
-
-fn load_highscores_and_lines() -> Option<(Vec<u32>, Vec<u32>)> {
-    if let Ok(content) = read_from_file(HIGHSCORE_FILE) {
-        let mut lines = content.splitn(2, "\n").map(|line| line_to_slice(line)).collect::<Vec<_>>();
-        if lines.len() == 2 {
-            let (lines_sent, highscores) = (lines.pop().unwrap(), lines.pop().unwrap());
-            Some((highscores, lines_sent))
-        } else {
-            None
-        }
-    } else {
-        None
-    }
-}
-
-fn update_vec(v: &mut Vec<u32>, value: u32) -> bool {
-    if v.len() < NB_HIGHSCORES {
-        v.push(value);
-        true
-    } else {
-        for entry in v.iter_mut() {
-            if value > *entry {
-                *entry = value;
-                return true;
+            "--puzzle" if i + 1 < args.len() => {
+                i += 1;
+                match Puzzle::load(&args[i]) {
+                    Ok(p) => puzzle = Some(p),
+                    Err(e) => {
+                        println!("Failed to load puzzle: {}", e);
+                        return
+                    }
+                }
+            }
+            "puzzles" => match puzzle::browse() {
+                Some(p) => puzzle = Some(p),
+                None => return,
+            },
+            arg => {
+                println!("Unknown argument \"{}\"", arg);
+                println!("{}", USAGE);
+                return
             }
         }
-        false
+        i += 1;
     }
-}
 
-fn print_game_information(tetris: &Tetris) {
-    let mut new_highest_highscore = true;
-    let mut new_highest_lines_sent = true;
-    if let Some((mut highscores, mut lines_sent)) = load_highscores_and_lines() {
-        new_highest_highscore = update_vec(&mut highscores, tetris.score);
-        new_highest_lines_sent = update_vec(&mut lines_sent, tetris.nb_lines);
-        if new_highest_highscore || new_highest_lines_sent {
-            save_highscores_and_lines(&highscores, &lines_sent);
-        }
-    } else {
-        save_highscores_and_lines(&[tetris.score], &[tetris.nb_lines]);
+    if let GameMode::Dig { lines, .. } = mode {
+        mode = GameMode::Dig {
+            lines: garbage.unwrap_or(lines),
+            messy,
+            rising: rising.unwrap_or(0),
+        };
     }
-    println!("Game over...");
-    println!("Score:           {}{}",
-             tetris.score,
-             if new_highest_highscore { " [NEW HIGHSCORE]"} else { "" });
-    println!("Number of lines: {}{}",
-             tetris.nb_lines,
-             if new_highest_lines_sent { " [NEW HIGHSCORE]"} else { "" });
-    println!("Current level:   {}", tetris.current_level);
-}
-
-fn is_time_over(tetris: &Tetris, timer: &SystemTime) -> bool {
-    match timer.elapsed() {
-        Ok(elapsed) => {
-            let millis = elapsed.as_secs() as u32 * 1000 + elapsed.subsec_nanos() / 1_000_000;
-            millis > LEVEL_TIMES[tetris.current_level as usize - 1]
-        }
-        Err(_) => false,
+    if let GameMode::Classic { start_level: level } = mode {
+        mode = GameMode::Classic { start_level: start_level.unwrap_or(level) };
     }
-}
 
-fn main() {
-    let mut tetris = Tetris::new();
-    let mut timer = SystemTime::now();
-    let sdl_context = sdl2::init().expect("SDL initialization failed");
-    let width = 600;
-    let height = 800;
-
-    let mut event_pump = sdl_context.event_pump().expect("Failed to get SDL event pump");
-
-    let grid_x = (width - TETRIS_HEIGHT as u32 * 10) as i32 / 2;
-    let grid_y = (height - TETRIS_HEIGHT as u32 * 16) as i32 / 2;
-
-    loop {
-        if is_time_over(&tetris, &timer) {
-            let mut make_permanent = false;
-            if let Some(ref mut piece) = tetris.current_piece {
-                let x = piece.x;
-                let y = piece.y + 1;
-                make_permanent = !piece.change_position(&tetris.game_map, x, y);
+    // Viewers only get the state of the game, which the script of a mod is not
+    // part of.
+    if mod_name.is_some() && spectate.is_some() {
+        println!("Mods can't be streamed to spectators, --spectate can't be used with --mod.");
+        return
+    }
+    let modded = match mod_name {
+        Some(name) => match start_mod(&name, piece_set.clone()) {
+            Ok(game) => Some(game),
+            Err(e) => {
+                println!("Failed to load the mod: {}", e);
+                return
             }
-            if make_permanent {
-                tetris.make_permanent();
+        },
+        None => None,
+    };
+    let saved = if new_game || puzzle.is_some() { None } else { offer_saved_game() };
+    let puzzle_file = puzzle.as_ref().map(|p| p.file_name.clone());
+    let saves_on_quit = puzzle.is_none() && bot.is_none() && modded.is_none() &&
+                        mode.can_be_continued();
+    // Bots don't get any attempt.
+    let profile = settings.profile.clone();
+    let ranked_daily = match mode {
+        // Everyone has to get the same game.
+        GameMode::Daily { .. } if bot.is_none() && settings.timing != Timing::default() => {
+            println!("The game speed or delays aren't the default ones, this game won't be ranked.");
+            false
+        }
+        GameMode::Daily { date } if bot.is_none() => {
+            let played = daily::has_played(date, &profile);
+            if played {
+                println!("{} already played the daily challenge of {}, this game won't be ranked.",
+                         profile, date);
             }
-            timer = SystemTime::now();
+            !played
         }
-
-        // We need to draw the tetris "grid" in here.
-
-        if tetris.current_piece.is_none() {
-            let current_piece = tetris.create_new_tetrimino();
-            if !current_piece.test_current_position(&tetris.game_map) {
-                print_game_information(&tetris);
-                break
+        _ => false,
+    };
+    let daily_profile = if ranked_daily { Some(profile.clone()) } else { None };
+    // The terminal has to be restored before we print anything.
+    if let Some(ref server) = spectate {
+        println!("Spectators can watch the game with `tetris watch {}`.", server.address());
+    }
+    let options = GameOptions {
+        mode, piece_set, bot, puzzle, saved, modded, settings, record, spectate, daily_profile
+    };
+    if let Some(result) = start(use_tui, options) {
+        if result.outcome == Outcome::Quit && saves_on_quit && save::exists() {
+            println!("Game saved, start the game again to continue it.");
+            return
+        }
+        print_game_information(&result);
+        if let GameMode::Daily { date } = result.tetris.mode {
+            if ranked_daily {
+                if let Err(e) = daily::save_attempt(date, &profile, result.tetris.score) {
+                    println!("Failed to save the daily score: {}", e);
+                }
             }
-            tetris.current_piece = Some(current_piece);
+            daily::print_leaderboards(date, Some(&profile));
         }
-        let mut quit = false;
-        if !handle_events(&mut tetris, &mut quit, &mut timer, &mut event_pump) {
-            if let Some(ref mut piece) = tetris.current_piece {
-                // We need to draw our current tetrimino in here.
+        stats::print(&result);
+        if let Some(file_name) = stats_file {
+            if let Err(e) = stats::export(&result, &file_name) {
+                println!("Failed to export the statistics to \"{}\": {}", file_name, e);
             }
         }
-        if quit {
-            print_game_information(&tetris);
-            break
+        if let (Some(file_name), Outcome::Won) = (puzzle_file, result.outcome) {
+            puzzle::mark_completed(&file_name);
         }
-
-        // We need to draw the game map in here.
-
-        sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
 }
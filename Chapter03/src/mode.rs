@@ -0,0 +1,340 @@
+use std::time::Duration;
+
+use classic::MAX_START_LEVEL;
+use daily::Date;
+use finesse::format_inputs;
+use highscores::Ranking;
+use puzzle::Goal;
+use save;
+use tetris::{Action, Tetris};
+
+pub const SPRINT_LINES: u32 = 40;
+pub const ULTRA_SECONDS: u64 = 120;
+pub const MARATHON_LINES: u32 = 150;
+pub const DIG_LINES: u32 = 10;
+/// The map has to keep some room for the pieces to spawn.
+pub const MAX_DIG_LINES: u32 = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameMode {
+    /// The original game: it only ends when the player tops out.
+    Endless,
+    /// Clear `SPRINT_LINES` lines as fast as possible.
+    Sprint,
+    /// Get the highest score in `ULTRA_SECONDS` seconds.
+    Ultra,
+    /// Clear `MARATHON_LINES` lines while the level goes up.
+    Marathon,
+    /// Clear all the garbage lines the game starts with. If `rising` isn't 0, a
+    /// new garbage line comes up every `rising` seconds.
+    Dig { lines: u32, messy: bool, rising: u32 },
+    /// Reach the goal of a puzzle file before running out of pieces.
+    Puzzle { goal: Goal },
+    /// Endless game at the first level, showing the pieces placed with more
+    /// inputs than needed and how to place them.
+    Finesse,
+    /// Rules written in a script of the "mods" directory, see `script`. The
+    /// script decides when the game is over.
+    Mod,
+    /// The NES version of the game, see `classic`. Levels start from 0 there.
+    Classic { start_level: u32 },
+    /// The challenge of `date`, whose pieces are the same for everyone since
+    /// they're drawn with the date as seed, see `daily`. It's played like the
+    /// ultra mode.
+    Daily { date: Date },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Won,
+    Lost(GameOver),
+    Quit,
+}
+
+/// Why a game got lost.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameOver {
+    /// A new piece overlapped the blocks of the map.
+    BlockOut,
+    /// A piece got locked entirely in the vanish zone, above the visible map.
+    LockOut,
+    /// Garbage pushed blocks out of the top of the map.
+    GarbageTopOut,
+    /// The pieces of a puzzle ran out before its goal was reached.
+    OutOfPieces,
+    /// The script of a mod ended the game.
+    ModRule,
+}
+
+impl GameOver {
+    pub fn description(self) -> &'static str {
+        match self {
+            GameOver::BlockOut => "block out",
+            GameOver::LockOut => "lock out",
+            GameOver::GarbageTopOut => "garbage top out",
+            GameOver::OutOfPieces => "out of pieces",
+            GameOver::ModRule => "rule of the mod",
+        }
+    }
+}
+
+pub struct GameResult {
+    pub tetris: Tetris,
+    pub time: Duration,
+    pub outcome: Outcome,
+    /// Whether the score can enter the high scores, see `Game::ranked`.
+    pub ranked: bool,
+}
+
+impl GameMode {
+    /// The daily challenge isn't there, its date has to be picked.
+    pub fn from_name(name: &str) -> Option<GameMode> {
+        match name {
+            "endless" => Some(GameMode::Endless),
+            "sprint" => Some(GameMode::Sprint),
+            "ultra" => Some(GameMode::Ultra),
+            "marathon" => Some(GameMode::Marathon),
+            "dig" => Some(GameMode::Dig { lines: DIG_LINES, messy: false, rising: 0 }),
+            "finesse" => Some(GameMode::Finesse),
+            "classic" => Some(GameMode::Classic { start_level: 0 }),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            GameMode::Endless => "endless",
+            GameMode::Sprint => "sprint",
+            GameMode::Ultra => "ultra",
+            GameMode::Marathon => "marathon",
+            GameMode::Dig { .. } => "dig",
+            GameMode::Puzzle { .. } => "puzzle",
+            GameMode::Finesse => "finesse",
+            GameMode::Mod => "mod",
+            GameMode::Classic { .. } => "classic",
+            GameMode::Daily { .. } => "daily",
+        }
+    }
+
+    /// Formats the mode as its name followed by its parameters.
+    pub fn to_save(self) -> String {
+        match self {
+            GameMode::Dig { lines, messy, rising } => format!("dig {} {} {}", lines, messy, rising),
+            GameMode::Puzzle { goal } => format!("puzzle {}", goal.to_save()),
+            GameMode::Classic { start_level } => format!("classic {}", start_level),
+            GameMode::Daily { date } => format!("daily {}", date),
+            mode => mode.name().to_string(),
+        }
+    }
+
+    /// Daily challenges aren't saved when quitting, see `can_be_continued`, and
+    /// the game doesn't save puzzles either, but replays start from a save of
+    /// the game. Mods can't be continued since their scripts aren't saved.
+    pub fn from_save(s: &str) -> save::Result<GameMode> {
+        let mut words = s.split_whitespace();
+        match words.next() {
+            Some("dig") => {
+                let values = words.collect::<Vec<_>>();
+                if values.len() != 3 {
+                    return Err("invalid dig mode".into());
+                }
+                let lines = save::parse::<u32>(values[0], "garbage lines")?;
+                if lines == 0 || lines > MAX_DIG_LINES {
+                    return Err("invalid number of garbage lines".into());
+                }
+                Ok(GameMode::Dig {
+                    lines,
+                    messy: save::parse(values[1], "garbage messiness")?,
+                    rising: save::parse(values[2], "rising delay")?,
+                })
+            }
+            Some("puzzle") => {
+                let goal = words.collect::<Vec<_>>().join(" ");
+                Goal::parse(&goal).map(|goal| GameMode::Puzzle { goal })
+                                  .ok_or_else(|| "invalid puzzle goal".into())
+            }
+            Some("classic") => {
+                let start_level = save::parse::<u32>(words.next().ok_or("missing start level")?,
+                                                     "start level")?;
+                if start_level > MAX_START_LEVEL {
+                    return Err("invalid start level".into());
+                }
+                Ok(GameMode::Classic { start_level })
+            }
+            Some("daily") => {
+                let date = words.next().and_then(Date::parse).ok_or("invalid date")?;
+                Ok(GameMode::Daily { date })
+            }
+            Some(name) => GameMode::from_name(name).ok_or_else(|| {
+                save::Error::Corrupted(format!("mode \"{}\" can't be continued", name))
+            }),
+            None => Err("missing mode".into()),
+        }
+    }
+
+    pub fn has_level_progression(&self) -> bool {
+        match *self {
+            GameMode::Endless | GameMode::Marathon | GameMode::Mod |
+            GameMode::Classic { .. } => true,
+            GameMode::Sprint | GameMode::Ultra | GameMode::Dig { .. } |
+            GameMode::Puzzle { .. } | GameMode::Finesse | GameMode::Daily { .. } => false,
+        }
+    }
+
+    /// Whether a game quit before its end is saved to be continued. Mods can't
+    /// be, and a daily challenge has to be played in one go.
+    pub fn can_be_continued(&self) -> bool {
+        match *self {
+            GameMode::Endless | GameMode::Sprint | GameMode::Ultra | GameMode::Marathon |
+            GameMode::Dig { .. } | GameMode::Puzzle { .. } | GameMode::Finesse |
+            GameMode::Classic { .. } => true,
+            GameMode::Mod | GameMode::Daily { .. } => false,
+        }
+    }
+
+    /// Whether a rotation or a hold pressed or held down while waiting for the
+    /// next piece applies to it when it spawns (IRS/IHS). The finesse mode
+    /// counts the inputs from the spawn, so it doesn't have them, and the NES
+    /// didn't have them.
+    pub fn has_initial_actions(&self) -> bool {
+        match *self {
+            GameMode::Endless | GameMode::Sprint | GameMode::Ultra | GameMode::Marathon |
+            GameMode::Dig { .. } | GameMode::Puzzle { .. } | GameMode::Mod |
+            GameMode::Daily { .. } => true,
+            GameMode::Finesse | GameMode::Classic { .. } => false,
+        }
+    }
+
+    /// Whether the player can do `action`: the classic mode has no hold and no
+    /// hard drop.
+    pub fn allows(&self, action: Action) -> bool {
+        match *self {
+            GameMode::Classic { .. } => action != Action::Hold && action != Action::HardDrop,
+            _ => true,
+        }
+    }
+
+    /// Checks whether the game is over. Topping out is handled by the engine
+    /// since it's the same for every mode.
+    pub fn check(&self, tetris: &Tetris, time: Duration) -> Option<Outcome> {
+        match *self {
+            GameMode::Endless | GameMode::Mod | GameMode::Classic { .. } => None,
+            GameMode::Sprint if tetris.nb_lines >= SPRINT_LINES => Some(Outcome::Won),
+            GameMode::Ultra | GameMode::Daily { .. } if time.as_secs() >= ULTRA_SECONDS => {
+                Some(Outcome::Won)
+            }
+            GameMode::Marathon if tetris.nb_lines >= MARATHON_LINES => Some(Outcome::Won),
+            GameMode::Dig { .. } if tetris.garbage_left() == 0 => Some(Outcome::Won),
+            GameMode::Puzzle { goal } if goal.is_reached(tetris) => Some(Outcome::Won),
+            _ => None,
+        }
+    }
+
+    /// Information displayed next to the game map.
+    pub fn hud(&self, tetris: &Tetris, time: Duration) -> Vec<(&'static str, String)> {
+        let mut hud = match *self {
+            GameMode::Endless | GameMode::Classic { .. } => {
+                vec![("Score", tetris.score.to_string()),
+                     ("Lines", tetris.nb_lines.to_string()),
+                     ("Level", tetris.current_level.to_string())]
+            }
+            GameMode::Sprint => vec![("Time", format_time(time)),
+                                     ("Lines", format!("{}/{}", tetris.nb_lines.min(SPRINT_LINES),
+                                                       SPRINT_LINES))],
+            GameMode::Ultra | GameMode::Daily { .. } => {
+                let left = Duration::from_secs(ULTRA_SECONDS).checked_sub(time)
+                                                             .unwrap_or_default();
+                let mut hud = vec![("Time", format_time(left)),
+                                   ("Score", tetris.score.to_string()),
+                                   ("Lines", tetris.nb_lines.to_string())];
+                if let GameMode::Daily { date } = *self {
+                    hud.insert(0, ("Daily", date.to_string()));
+                }
+                hud
+            }
+            GameMode::Marathon => vec![("Score", tetris.score.to_string()),
+                                       ("Lines", format!("{}/{}",
+                                                         tetris.nb_lines.min(MARATHON_LINES),
+                                                         MARATHON_LINES)),
+                                       ("Level", tetris.current_level.to_string())],
+            GameMode::Dig { .. } => vec![("Time", format_time(time)),
+                                         ("Garbage", tetris.garbage_left().to_string()),
+                                         ("Pieces", tetris.nb_pieces.to_string())],
+            GameMode::Puzzle { goal } => vec![("Goal", goal.to_string()),
+                                              ("Pieces left",
+                                               tetris.pieces_left().unwrap_or(0).to_string())],
+            GameMode::Finesse => {
+                let mut hud = vec![("Faults", format!("{}/{}", tetris.stats.finesse_faults,
+                                                      tetris.nb_pieces))];
+                if let Some(ref fault) = tetris.finesse.last_fault {
+                    hud.push(("Last fault", format_inputs(&fault.used)));
+                    hud.push(("Optimal", format_inputs(&fault.optimal)));
+                }
+                hud
+            }
+            GameMode::Mod => vec![("Time", format_time(time)),
+                                  ("Score", tetris.score.to_string()),
+                                  ("Lines", tetris.nb_lines.to_string()),
+                                  ("Level", tetris.current_level.to_string())],
+        };
+        if let Some(id) = tetris.held_piece {
+            hud.push(("Hold", tetris.piece_set.pieces[id as usize].name.clone()));
+        }
+        hud
+    }
+
+    /// Time between two rising garbage lines.
+    pub fn rising_garbage(&self) -> Option<Duration> {
+        match *self {
+            GameMode::Dig { rising, .. } if rising > 0 => Some(Duration::from_secs(rising as u64)),
+            _ => None,
+        }
+    }
+
+    /// File of the mode's high-score table and how it's sorted. The endless mode
+    /// keeps the original "scores.txt" file, puzzles don't have any and daily
+    /// challenges have their own leaderboards.
+    pub fn highscore_table(&self) -> Option<(String, Ranking)> {
+        match *self {
+            GameMode::Endless | GameMode::Puzzle { .. } | GameMode::Finesse |
+            GameMode::Mod | GameMode::Daily { .. } => None,
+            GameMode::Sprint => Some(("scores_sprint.txt".to_string(), Ranking::LowestFirst)),
+            GameMode::Ultra => Some(("scores_ultra.txt".to_string(), Ranking::HighestFirst)),
+            GameMode::Marathon => Some(("scores_marathon.txt".to_string(), Ranking::HighestFirst)),
+            GameMode::Classic { .. } => Some(("scores_classic.txt".to_string(), Ranking::HighestFirst)),
+            // Every setup gets its own table since they can't be compared.
+            GameMode::Dig { lines, messy, rising } => {
+                let mut name = format!("scores_dig_{}", lines);
+                if messy {
+                    name.push_str("_messy");
+                }
+                if rising > 0 {
+                    name.push_str(&format!("_rising{}", rising));
+                }
+                Some((name + ".txt", Ranking::LowestFirst))
+            }
+        }
+    }
+
+    /// Value entered in the high-score table, if the game counts.
+    pub fn ranked_value(&self, result: &GameResult) -> Option<u32> {
+        match (*self, result.outcome) {
+            (GameMode::Sprint, Outcome::Won) |
+            (GameMode::Dig { .. }, Outcome::Won) => Some(as_millis(result.time)),
+            (GameMode::Ultra, Outcome::Won) => Some(result.tetris.score),
+            (GameMode::Marathon, Outcome::Won) |
+            (GameMode::Marathon, Outcome::Lost(_)) |
+            (GameMode::Classic { .. }, Outcome::Lost(_)) => Some(result.tetris.score),
+            _ => None,
+        }
+    }
+}
+
+pub fn as_millis(time: Duration) -> u32 {
+    time.as_secs() as u32 * 1000 + time.subsec_millis()
+}
+
+/// Formats a duration as "mm:ss.mmm".
+pub fn format_time(time: Duration) -> String {
+    format!("{:02}:{:02}.{:03}", time.as_secs() / 60, time.as_secs() % 60, time.subsec_millis())
+}
@@ -0,0 +1,22 @@
+// Subsystems which react to the game, such as sounds or achievements, don't have
+// to change the rules: they implement `GameObserver` and are registered with
+// `Tetris::add_observer`, and the engine calls them as the game goes.
+
+use mode::GameOver;
+use tetrimino::Tetrimino;
+
+/// Gets notified of what happens in a game. Every method does nothing by
+/// default, so that observers only implement what they need.
+pub trait GameObserver {
+    /// A new piece appeared.
+    fn on_spawn(&mut self, _piece: &Tetrimino) {}
+    /// The current piece moved, sideways or down, `piece` being where it went.
+    fn on_move(&mut self, _piece: &Tetrimino) {}
+    fn on_rotate(&mut self, _piece: &Tetrimino) {}
+    /// The piece got locked into the map.
+    fn on_lock(&mut self, _piece: &Tetrimino) {}
+    /// `rows` are the lines which were removed, as they were numbered before.
+    fn on_lines_cleared(&mut self, _rows: &[usize], _tspin: bool, _perfect_clear: bool) {}
+    fn on_level_up(&mut self, _level: u32) {}
+    fn on_game_over(&mut self, _reason: GameOver) {}
+}
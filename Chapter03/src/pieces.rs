@@ -0,0 +1,298 @@
+// The pieces of a game come from a definition file like "pieces/standard.txt":
+//
+//     # Lines starting with '#' are ignored.
+//     kicks: 0,0 -1,0 1,0      default kicks of the pieces of the file
+//
+//     piece: T
+//     color: 160 0 240
+//     spawn: 4 0               column of the first state, and how many lines lower
+//                              than right above the visible lines it spawns
+//     kicks 2: 0,0 0,-1        kicks used when rotating from state 2
+//     state:                   rotation states, in clockwise order
+//     XXX
+//     .X.
+//     state:
+//     ...
+//
+// Instead of listing its states, a piece can give a single one followed by
+// "rotations: N" to get it rotated clockwise N - 1 times.
+
+use garbage::{GARBAGE, GARBAGE_COLOR};
+use board::{VANISH_ZONE, WIDTH};
+use tetrimino::{self, Kicks, Mask, States, Tetrimino};
+use read_from_file;
+
+/// Names given to the sets built in the game.
+pub const STANDARD: &str = "standard";
+pub const CLASSIC: &str = "classic";
+/// Pieces are saved as a single base 36 digit.
+pub const MAX_PIECES: usize = 36;
+
+const STANDARD_DEFINITION: &str = include_str!("../pieces/standard.txt");
+const CLASSIC_DEFINITION: &str = include_str!("../pieces/classic.txt");
+const DEFAULT_KICKS: [(isize, isize); 6] = [(0, 0), (-1, 0), (1, 0), (-2, 0), (2, 0), (-3, 0)];
+
+#[derive(Clone, Debug)]
+pub struct PieceDefinition {
+    pub name: String,
+    pub color: (u8, u8, u8),
+    spawn: (isize, usize),
+    states: States,
+    masks: Vec<Mask>,
+    kicks: Vec<Kicks>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PieceSet {
+    /// File the set was loaded from, or `STANDARD` or `CLASSIC`.
+    pub source: String,
+    /// Content of the definition file.
+    pub definition: String,
+    pub pieces: Vec<PieceDefinition>,
+}
+
+impl PieceSet {
+    /// The seven tetriminoes.
+    pub fn standard() -> PieceSet {
+        PieceSet::parse(STANDARD, STANDARD_DEFINITION).expect("invalid standard piece set")
+    }
+
+    /// The seven tetriminoes of the NES, without kicks.
+    pub fn classic() -> PieceSet {
+        PieceSet::parse(CLASSIC, CLASSIC_DEFINITION).expect("invalid classic piece set")
+    }
+
+    /// Loads a set from a definition file, `STANDARD` and `CLASSIC` giving the
+    /// built-in ones.
+    pub fn load(file_name: &str) -> Result<PieceSet, String> {
+        match file_name {
+            STANDARD => return Ok(PieceSet::standard()),
+            CLASSIC => return Ok(PieceSet::classic()),
+            _ => {}
+        }
+        read_from_file(file_name).map_err(|e| e.to_string())
+                                 .and_then(|content| PieceSet::parse(file_name, &content))
+                                 .map_err(|e| format!("{}: {}", file_name, e))
+    }
+
+    /// Reads a set from the `content` of a definition file, `source` telling
+    /// where it came from.
+    pub fn parse(source: &str, content: &str) -> Result<PieceSet, String> {
+        let mut pieces = Vec::new();
+        let mut default_kicks = DEFAULT_KICKS.to_vec();
+        let mut current: Option<Builder> = None;
+        let mut in_state = false;
+
+        for (nb, line) in content.lines().enumerate() {
+            let line = line.trim();
+            let error = |message: String| Err(format!("line {}: {}", nb + 1, message));
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            if !line.contains(':') {
+                // A line of the state being defined.
+                match current {
+                    Some(ref mut piece) if in_state => {
+                        let cells = line.chars().map(|c| (c != '.') as u8).collect();
+                        piece.states.last_mut().expect("no state").push(cells);
+                        continue
+                    }
+                    _ => return error(format!("expected \"key: value\", got \"{}\"", line)),
+                }
+            }
+            in_state = false;
+            let pos = line.find(':').expect("no colon");
+            let (key, value) = (line[..pos].trim(), line[pos + 1..].trim());
+            if key == "piece" {
+                if let Some(piece) = current.take() {
+                    pieces.push(piece.build(&default_kicks)?);
+                }
+                if value.is_empty() || pieces.iter().any(|p: &PieceDefinition| p.name == value) {
+                    return error(format!("invalid or duplicated piece name \"{}\"", value));
+                }
+                current = Some(Builder::new(value, nb + 1));
+                continue
+            }
+            let piece = match current {
+                Some(ref mut piece) => piece,
+                None if key == "kicks" => {
+                    default_kicks = parse_kicks(value).map_err(|e| format!("line {}: {}", nb + 1, e))?;
+                    continue
+                }
+                None => return error(format!("\"{}\" has to come after a \"piece\" line", key)),
+            };
+            let values = value.split_whitespace().collect::<Vec<_>>();
+            match key {
+                "color" => {
+                    let color = values.iter().map(|v| v.parse::<u8>()).collect::<Result<Vec<_>, _>>();
+                    match color {
+                        Ok(ref c) if c.len() == 3 => piece.color = Some((c[0], c[1], c[2])),
+                        _ => return error(format!("invalid color \"{}\"", value)),
+                    }
+                }
+                "spawn" => {
+                    match (values.first().and_then(|x| x.parse().ok()),
+                           values.get(1).and_then(|y| y.parse().ok())) {
+                        (Some(x), Some(y)) if values.len() == 2 => piece.spawn = (x, y),
+                        _ => return error(format!("invalid spawn position \"{}\"", value)),
+                    }
+                }
+                "rotations" => match value.parse::<usize>() {
+                    Ok(n) if (1..=4).contains(&n) => piece.rotations = n,
+                    _ => return error(format!("invalid number of rotations \"{}\"", value)),
+                },
+                "state" => {
+                    piece.states.push(Vec::new());
+                    in_state = true;
+                }
+                _ if key.starts_with("kicks ") => {
+                    let state = key["kicks ".len()..].trim().parse::<usize>()
+                                                     .map_err(|_| format!("line {}: invalid state", nb + 1))?;
+                    let kicks = parse_kicks(value).map_err(|e| format!("line {}: {}", nb + 1, e))?;
+                    piece.kicks.push((state, kicks));
+                }
+                "kicks" => {
+                    piece.default_kicks = Some(parse_kicks(value).map_err(|e| format!("line {}: {}", nb + 1, e))?);
+                }
+                _ => return error(format!("unknown key \"{}\"", key)),
+            }
+        }
+        if let Some(piece) = current.take() {
+            pieces.push(piece.build(&default_kicks)?);
+        }
+        if pieces.is_empty() || pieces.len() > MAX_PIECES {
+            return Err(format!("a set needs between 1 and {} pieces", MAX_PIECES));
+        }
+        Ok(PieceSet {
+            source: source.to_string(),
+            definition: content.to_string(),
+            pieces,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.pieces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pieces.is_empty()
+    }
+
+    pub fn find(&self, name: &str) -> Option<u8> {
+        self.pieces.iter().position(|piece| piece.name == name).map(|id| id as u8)
+    }
+
+    /// Creates the piece `id` at its spawn position. The cells of the piece get
+    /// `id + 1` as value.
+    pub fn spawn(&self, id: u8) -> Option<Tetrimino> {
+        self.pieces.get(id as usize).map(|piece| {
+            Tetrimino {
+                id,
+                states: piece.states.iter().map(|state| {
+                    state.iter().map(|line| line.iter().map(|&c| c * (id + 1)).collect()).collect()
+                }).collect(),
+                masks: piece.masks.clone(),
+                kicks: piece.kicks.clone(),
+                x: piece.spawn.0,
+                // The piece spawns right above the visible lines.
+                y: (VANISH_ZONE + piece.spawn.1).saturating_sub(piece.states[0].len()),
+                current_state: 0,
+            }
+        })
+    }
+
+    /// Colour of a cell of the game map.
+    pub fn color(&self, cell: u8) -> (u8, u8, u8) {
+        match cell {
+            GARBAGE => GARBAGE_COLOR,
+            _ => self.pieces.get(cell as usize - 1).map(|piece| piece.color).unwrap_or(GARBAGE_COLOR),
+        }
+    }
+}
+
+/// Piece definition being read.
+struct Builder {
+    name: String,
+    line: usize,
+    color: Option<(u8, u8, u8)>,
+    spawn: (isize, usize),
+    rotations: usize,
+    states: States,
+    default_kicks: Option<Kicks>,
+    kicks: Vec<(usize, Kicks)>,
+}
+
+impl Builder {
+    fn new(name: &str, line: usize) -> Builder {
+        Builder {
+            name: name.to_string(),
+            line,
+            color: None,
+            spawn: (4, 0),
+            rotations: 1,
+            states: Vec::new(),
+            default_kicks: None,
+            kicks: Vec::new(),
+        }
+    }
+
+    fn build(mut self, default_kicks: &[(isize, isize)]) -> Result<PieceDefinition, String> {
+        let prefix = format!("line {}: piece \"{}\"", self.line, self.name);
+        let error = |message: &str| Err(format!("{} {}", prefix, message));
+        if self.states.is_empty() {
+            return error("has no state");
+        }
+        if self.rotations > 1 {
+            if self.states.len() > 1 {
+                return error("can't have both several states and rotations");
+            }
+            for _ in 1..self.rotations {
+                let rotated = rotate_clockwise(self.states.last().expect("no state"));
+                self.states.push(rotated);
+            }
+        }
+        if self.states.iter().any(|state| !state.iter().any(|line| line.contains(&1))) {
+            return error("has an empty state");
+        }
+        if self.states.iter().any(|state| state.iter().any(|line| line.len() > WIDTH)) {
+            return error(&format!("is wider than the map ({} cells)", WIDTH));
+        }
+        let color = match self.color {
+            Some(color) => color,
+            None => return error("has no color"),
+        };
+        let default_kicks = self.default_kicks.unwrap_or_else(|| default_kicks.to_vec());
+        let mut kicks = vec![default_kicks; self.states.len()];
+        for (state, state_kicks) in self.kicks {
+            if state >= kicks.len() {
+                return error(&format!("has kicks for unknown state {}", state));
+            }
+            kicks[state] = state_kicks;
+        }
+        Ok(PieceDefinition {
+            name: self.name,
+            color,
+            spawn: self.spawn,
+            masks: self.states.iter().map(tetrimino::mask).collect(),
+            states: self.states,
+            kicks,
+        })
+    }
+}
+
+fn parse_kicks(value: &str) -> Result<Kicks, String> {
+    value.split_whitespace().map(|kick| {
+        let mut parts = kick.split(',').map(|n| n.trim().parse::<isize>());
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(x)), Some(Ok(y)), None) => Ok((x, y)),
+            _ => Err(format!("invalid kick \"{}\"", kick)),
+        }
+    }).collect()
+}
+
+fn rotate_clockwise(state: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let width = state.iter().map(|line| line.len()).max().unwrap_or(0);
+    (0..width).map(|x| {
+        state.iter().rev().map(|line| line.get(x).cloned().unwrap_or(0)).collect()
+    }).collect()
+}
@@ -0,0 +1,261 @@
+// A puzzle is a text file like this one:
+//
+//     # Lines starting with '#' are ignored.
+//     name: Right wall T-spin
+//     goal: tspin-double
+//     pieces: T
+//     board:
+//     XXXXXXXX..
+//     XXXXXXXXX.
+//
+// The goal is one of "lines N", "perfect-clear", "tspin-single", "tspin-double"
+// and "tspin-triple". The pieces are given in the order they'll come. The board
+// comes last and fills the bottom of the game map: '.' is an empty cell, 'X' a
+// garbage cell and a tetrimino letter a cell of that tetrimino's color. Puzzles
+// always use the standard tetriminoes.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+
+use garbage::GARBAGE;
+use mode::{GameMode, MAX_DIG_LINES};
+use pieces::PieceSet;
+use tetris::Tetris;
+use {read_from_file, write_into_file};
+
+const COMPLETED_FILE: &str = "puzzles_completed.txt";
+const PUZZLES_DIR: &str = "puzzles";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Goal {
+    Lines(u32),
+    PerfectClear,
+    /// Number of lines the T-spin has to clear.
+    TSpin(u32),
+}
+
+impl Goal {
+    pub fn parse(s: &str) -> Option<Goal> {
+        let words = s.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            ["lines", nb] => nb.parse().ok().filter(|&nb| nb > 0).map(Goal::Lines),
+            ["perfect-clear"] => Some(Goal::PerfectClear),
+            ["tspin-single"] => Some(Goal::TSpin(1)),
+            ["tspin-double"] => Some(Goal::TSpin(2)),
+            ["tspin-triple"] => Some(Goal::TSpin(3)),
+            _ => None,
+        }
+    }
+
+    /// Formats the goal the way `parse` reads it.
+    pub fn to_save(self) -> String {
+        match self {
+            Goal::Lines(nb) => format!("lines {}", nb),
+            Goal::PerfectClear => "perfect-clear".to_string(),
+            Goal::TSpin(1) => "tspin-single".to_string(),
+            Goal::TSpin(2) => "tspin-double".to_string(),
+            Goal::TSpin(_) => "tspin-triple".to_string(),
+        }
+    }
+
+    pub fn is_reached(&self, tetris: &Tetris) -> bool {
+        match *self {
+            Goal::Lines(nb) => tetris.nb_lines >= nb,
+            Goal::PerfectClear => tetris.nb_perfect_clears > 0,
+            Goal::TSpin(nb) => tetris.nb_tspins[nb as usize] > 0,
+        }
+    }
+}
+
+impl fmt::Display for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Goal::Lines(nb) => write!(f, "clear {} lines", nb),
+            Goal::PerfectClear => write!(f, "perfect clear"),
+            Goal::TSpin(1) => write!(f, "T-spin single"),
+            Goal::TSpin(2) => write!(f, "T-spin double"),
+            Goal::TSpin(_) => write!(f, "T-spin triple"),
+        }
+    }
+}
+
+pub struct Puzzle {
+    pub file_name: String,
+    pub name: String,
+    pub goal: Goal,
+    /// Ids of the pieces, as given to `Tetris::set_piece_sequence`.
+    pub pieces: Vec<u8>,
+    /// Bottom lines of the game map.
+    pub board: Vec<Vec<u8>>,
+}
+
+impl Puzzle {
+    pub fn load(file_name: &str) -> Result<Puzzle, String> {
+        read_from_file(file_name).map_err(|e| e.to_string())
+                                 .and_then(|content| Puzzle::parse(file_name, &content))
+                                 .map_err(|e| format!("{}: {}", file_name, e))
+    }
+
+    fn parse(file_name: &str, content: &str) -> Result<Puzzle, String> {
+        let piece_set = PieceSet::standard();
+        let mut name = None;
+        let mut goal = None;
+        let mut pieces = None;
+        let mut board = Vec::new();
+        let mut in_board = false;
+        for (nb, line) in content.lines().enumerate() {
+            let line = line.trim();
+            let error = |message: String| Err(format!("line {}: {}", nb + 1, message));
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            if in_board {
+                if line.len() != 10 {
+                    return error(format!("board lines must be 10 cells wide, got {}", line.len()));
+                }
+                let mut cells = Vec::new();
+                for c in line.chars() {
+                    cells.push(match c {
+                        '.' => 0,
+                        'X' => GARBAGE,
+                        c => match piece_set.find(&c.to_string()) {
+                            Some(id) => id + 1,
+                            None => return error(format!("unknown cell '{}'", c)),
+                        },
+                    });
+                }
+                if !cells.contains(&0) {
+                    return error("board lines can't be full".to_string());
+                }
+                board.push(cells);
+                continue
+            }
+            let (key, value) = match line.find(':') {
+                Some(pos) => (line[..pos].trim(), line[pos + 1..].trim()),
+                None => return error(format!("expected \"key: value\", got \"{}\"", line)),
+            };
+            match key {
+                "name" => name = Some(value.to_string()),
+                "goal" => match Goal::parse(value) {
+                    Some(g) => goal = Some(g),
+                    None => return error(format!("unknown goal \"{}\"", value)),
+                },
+                "pieces" => {
+                    let mut ids = Vec::new();
+                    for piece in value.split_whitespace() {
+                        match piece_set.find(piece) {
+                            Some(id) => ids.push(id),
+                            None => return error(format!("unknown piece \"{}\"", piece)),
+                        }
+                    }
+                    pieces = Some(ids);
+                }
+                "board" => in_board = true,
+                _ => return error(format!("unknown key \"{}\"", key)),
+            }
+        }
+        if board.len() > MAX_DIG_LINES as usize {
+            return Err(format!("the board can't have more than {} lines", MAX_DIG_LINES));
+        }
+        let pieces = pieces.ok_or_else(|| "missing \"pieces\"".to_string())?;
+        if pieces.is_empty() {
+            return Err("there has to be at least one piece".to_string());
+        }
+        Ok(Puzzle {
+            file_name: file_name.to_string(),
+            name: name.unwrap_or_else(|| file_name.to_string()),
+            goal: goal.ok_or_else(|| "missing \"goal\"".to_string())?,
+            pieces,
+            board,
+        })
+    }
+
+    pub fn start(&self) -> Tetris {
+        let mut tetris = Tetris::new(GameMode::Puzzle { goal: self.goal }, PieceSet::standard());
+        let first_line = tetris.game_map.height() - self.board.len();
+        for (y, line) in self.board.iter().enumerate() {
+            for (x, &cell) in line.iter().enumerate() {
+                tetris.game_map.set(x, first_line + y, cell);
+            }
+        }
+        tetris.set_piece_sequence(self.pieces.clone());
+        tetris
+    }
+}
+
+fn completed_puzzles() -> Vec<String> {
+    read_from_file(COMPLETED_FILE).map(|content| content.lines().map(|l| l.to_string()).collect())
+                                  .unwrap_or_default()
+}
+
+pub fn mark_completed(file_name: &str) {
+    let mut completed = completed_puzzles();
+    if !completed.iter().any(|f| f == file_name) {
+        completed.push(file_name.to_string());
+        if let Err(e) = write_into_file(&(completed.join("\n") + "\n"), COMPLETED_FILE) {
+            println!("Failed to save puzzle completion: {}", e);
+        }
+    }
+}
+
+/// Sorted entries of `dir`, either its sub-directories or its files.
+fn list_dir(dir: &str, directories: bool) -> Vec<String> {
+    let mut entries = fs::read_dir(dir).map(|entries| {
+        entries.filter_map(|entry| entry.ok())
+               .filter(|entry| entry.path().is_dir() == directories)
+               .map(|entry| entry.path().to_string_lossy().into_owned())
+               .collect::<Vec<_>>()
+    }).unwrap_or_default();
+    entries.sort();
+    entries
+}
+
+/// Prints `choices` and returns the one picked by the player.
+fn pick(choices: &[String]) -> Option<usize> {
+    for (i, choice) in choices.iter().enumerate() {
+        println!("{:>3}. {}", i + 1, choice);
+    }
+    print!("Choice (empty to quit): ");
+    io::stdout().flush().ok()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line).ok()?;
+    match line.trim().parse::<usize>() {
+        Ok(nb) if nb >= 1 && nb <= choices.len() => Some(nb - 1),
+        _ => None,
+    }
+}
+
+/// Lets the player pick a pack in the "puzzles" directory, then a puzzle in it.
+pub fn browse() -> Option<Puzzle> {
+    let packs = list_dir(PUZZLES_DIR, true);
+    if packs.is_empty() {
+        println!("No puzzle pack found in \"{}\"", PUZZLES_DIR);
+        return None;
+    }
+    let completed = completed_puzzles();
+    let is_completed = |file: &String| completed.contains(file);
+    let descriptions = packs.iter().map(|pack| {
+        let files = list_dir(pack, false);
+        format!("{} ({}/{} completed)", pack, files.iter().filter(|f| is_completed(f)).count(),
+                files.len())
+    }).collect::<Vec<_>>();
+    let pack = &packs[pick(&descriptions)?];
+
+    let files = list_dir(pack, false);
+    let puzzles = files.iter().map(|file| Puzzle::load(file)).collect::<Vec<_>>();
+    let descriptions = files.iter().zip(puzzles.iter()).map(|(file, puzzle)| {
+        let mark = if is_completed(file) { "[x]" } else { "[ ]" };
+        match *puzzle {
+            Ok(ref puzzle) => format!("{} {} ({})", mark, puzzle.name, puzzle.goal),
+            Err(ref e) => format!("{} invalid puzzle: {}", mark, e),
+        }
+    }).collect::<Vec<_>>();
+    match puzzles.into_iter().nth(pick(&descriptions)?)? {
+        Ok(puzzle) => Some(puzzle),
+        Err(e) => {
+            println!("{}", e);
+            None
+        }
+    }
+}
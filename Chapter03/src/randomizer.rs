@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+
+use save::{self, parse, parse_list};
+
+// We don't use the `rand` generators here: games have to be reproducible from a
+// seed and the generator state has to stay readable.
+
+#[derive(Clone, Debug)]
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> XorShift {
+        // Spread the seed bits so that close seeds don't give close sequences.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        XorShift {
+            // A zero state would only ever generate zeros.
+            state: if z == 0 { 1 } else { z },
+        }
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn from_state(state: u64) -> save::Result<XorShift> {
+        if state == 0 {
+            return Err("generator state can't be 0".into());
+        }
+        Ok(XorShift { state })
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Returns a number in `[low, high)`.
+    pub fn gen_range(&mut self, low: u32, high: u32) -> u32 {
+        low + self.next_u32() % (high - low)
+    }
+
+    /// Returns a number in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Value of `Randomizer::previous` before the first piece.
+const NO_PIECE: u8 = u8::MAX;
+
+#[derive(Clone, Debug)]
+pub struct Randomizer {
+    rng: XorShift,
+    previous: u8,
+    /// Whether the pieces are drawn like on the NES, see `next_piece`.
+    nes: bool,
+    // When set, the pieces come from here instead of from `rng`.
+    sequence: Option<VecDeque<u8>>,
+}
+
+impl Randomizer {
+    pub fn new(seed: u64) -> Randomizer {
+        Randomizer {
+            rng: XorShift::new(seed),
+            previous: NO_PIECE,
+            nes: false,
+            sequence: None,
+        }
+    }
+
+    /// Draws the pieces like the NES version of the game.
+    pub fn nes(seed: u64) -> Randomizer {
+        Randomizer {
+            nes: true,
+            ..Randomizer::new(seed)
+        }
+    }
+
+    /// Gives the pieces of `sequence` in order, then nothing.
+    pub fn with_sequence(sequence: Vec<u8>) -> Randomizer {
+        Randomizer {
+            sequence: Some(sequence.into_iter().collect()),
+            ..Randomizer::new(0)
+        }
+    }
+
+    pub fn pieces_left(&self) -> Option<usize> {
+        self.sequence.as_ref().map(|sequence| sequence.len())
+    }
+
+    /// Formats the randomizer as "STATE PREVIOUS random", "STATE PREVIOUS nes"
+    /// or, with a fixed sequence, "STATE PREVIOUS sequence PIECES...".
+    pub fn to_save(&self) -> String {
+        let mut s = format!("{} {}", self.rng.state(), self.previous);
+        match self.sequence {
+            Some(ref sequence) => {
+                s.push_str(" sequence");
+                for piece in sequence {
+                    s.push_str(&format!(" {}", piece));
+                }
+            }
+            None if self.nes => s.push_str(" nes"),
+            None => s.push_str(" random"),
+        }
+        s
+    }
+
+    /// `nb_pieces` is the size of the piece set.
+    pub fn from_save(s: &str, nb_pieces: usize) -> save::Result<Randomizer> {
+        let values = s.split_whitespace().collect::<Vec<_>>();
+        if values.len() < 3 {
+            return Err("incomplete randomizer".into());
+        }
+        let sequence = match values[2] {
+            "random" | "nes" => None,
+            "sequence" => {
+                let pieces = parse_list::<u8>(&values[3..].join(" "), None, "piece")?;
+                if pieces.iter().any(|&piece| piece as usize >= nb_pieces) {
+                    return Err("invalid piece in sequence".into());
+                }
+                Some(pieces.into_iter().collect())
+            }
+            _ => return Err("invalid randomizer".into()),
+        };
+        Ok(Randomizer {
+            rng: XorShift::from_state(parse(values[0], "generator state")?)?,
+            previous: parse(values[1], "piece")?,
+            nes: values[2] == "nes",
+            sequence,
+        })
+    }
+
+    /// Picks the id of the next tetrimino among `nb_pieces`. If we get the same
+    /// piece twice in a row, we roll the dice one more time. The NES rolls a die
+    /// with one more face than there are pieces, the extra face also giving a
+    /// second roll.
+    pub fn next_piece(&mut self, nb_pieces: usize) -> Option<u8> {
+        if let Some(ref mut sequence) = self.sequence {
+            return sequence.pop_front();
+        }
+        let faces = nb_pieces as u32 + self.nes as u32;
+        let mut nb = self.rng.gen_range(0, faces) as u8;
+        if nb == self.previous || nb as usize == nb_pieces {
+            nb = self.rng.gen_range(0, nb_pieces as u32) as u8;
+        }
+        self.previous = nb;
+        Some(nb)
+    }
+
+    /// The piece `next_piece` would give, without drawing it.
+    pub fn peek(&self, nb_pieces: usize) -> Option<u8> {
+        self.clone().next_piece(nb_pieces)
+    }
+}
@@ -0,0 +1,167 @@
+// The game only depends on its starting state and on the actions applied at each
+// tick, so that's all a replay holds:
+//
+//     replay: 2
+//     are: 0
+//     line_clear_delay: 300
+//     speed: 100           speed of the falling pieces in percent, missing
+//                          from the replays of version 1
+//     version: 8           the starting state, as in a save file
+//     ...
+//     ticks: 3600          length of the game
+//     actions: 12:L 40:RR 41:H
+//     held: 38:U 52:       missing from the replays of versions 1 and 2
+//
+// Actions are grouped by tick, one letter each: L(eft), R(ight), D(own, soft
+// drop), U(p, rotation), H(ard drop) and C (hold, from its key). The held keys
+// are written down when they change, a tick without letters meaning that none
+// is held anymore.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::time::Duration;
+
+use game::{Game, Timing, MAX_SPEED, MIN_SPEED};
+use mode::as_millis;
+use save::{self, Fields};
+use tetris::Action;
+use {read_from_file, write_into_file};
+
+const REPLAY_VERSION: u32 = 3;
+
+fn action_to_char(action: Action) -> char {
+    match action {
+        Action::MoveLeft => 'L',
+        Action::MoveRight => 'R',
+        Action::SoftDrop => 'D',
+        Action::Rotate => 'U',
+        Action::HardDrop => 'H',
+        Action::Hold => 'C',
+    }
+}
+
+fn char_to_action(c: char) -> Option<Action> {
+    match c {
+        'L' => Some(Action::MoveLeft),
+        'R' => Some(Action::MoveRight),
+        'D' => Some(Action::SoftDrop),
+        'U' => Some(Action::Rotate),
+        'H' => Some(Action::HardDrop),
+        'C' => Some(Action::Hold),
+        _ => None,
+    }
+}
+
+/// Writes down a game as it's played.
+pub struct Recorder {
+    start: String,
+    timing: Timing,
+    ticks: u32,
+    actions: Vec<(u32, Vec<Action>)>,
+    held: Vec<(u32, Vec<Action>)>,
+}
+
+impl Recorder {
+    /// `game` has to be in its starting state, with its timing set.
+    pub fn new(game: &Game) -> Recorder {
+        Recorder {
+            start: save::to_save(game),
+            timing: game.timing,
+            ticks: 0,
+            actions: Vec::new(),
+            held: Vec::new(),
+        }
+    }
+
+    /// Has to be called for every tick of the game, with the actions given to
+    /// `Game::tick` and to `Game::set_held`.
+    pub fn tick(&mut self, actions: &[Action], held: &[Action]) {
+        if !actions.is_empty() {
+            self.actions.push((self.ticks, actions.to_vec()));
+        }
+        let previous = self.held.last().map(|(_, held)| held.as_slice()).unwrap_or(&[]);
+        if held != previous {
+            self.held.push((self.ticks, held.to_vec()));
+        }
+        self.ticks += 1;
+    }
+
+    pub fn save(&self, file_name: &str) -> io::Result<()> {
+        let content = format!("replay: {}\nare: {}\nline_clear_delay: {}\nspeed: {}\n{}ticks: {}\n\
+                               actions: {}\nheld: {}\n",
+                              REPLAY_VERSION, as_millis(self.timing.are),
+                              as_millis(self.timing.line_clear_delay), self.timing.speed, self.start,
+                              self.ticks, actions_to_string(&self.actions),
+                              actions_to_string(&self.held));
+        write_into_file(&content, file_name)
+    }
+}
+
+fn actions_to_string(actions: &[(u32, Vec<Action>)]) -> String {
+    actions.iter().map(|&(tick, ref actions)| {
+        format!("{}:{}", tick, actions.iter().map(|&a| action_to_char(a)).collect::<String>())
+    }).collect::<Vec<_>>().join(" ")
+}
+
+fn parse_actions(value: &str) -> save::Result<Vec<(u32, Vec<Action>)>> {
+    value.split_whitespace().map(|entry| {
+        let pos = entry.find(':').ok_or("invalid actions")?;
+        let tick = save::parse(&entry[..pos], "tick")?;
+        let actions = entry[pos + 1..].chars().map(char_to_action)
+                                      .collect::<Option<Vec<_>>>()
+                                      .ok_or("invalid action")?;
+        Ok((tick, actions))
+    }).collect()
+}
+
+/// The actions of the player, by tick.
+pub struct Actions {
+    pressed: HashMap<u32, Vec<Action>>,
+    /// The held keys from each tick they changed at.
+    held: BTreeMap<u32, Vec<Action>>,
+}
+
+impl Actions {
+    pub fn at(&self, tick: u32) -> &[Action] {
+        self.pressed.get(&tick).map(|actions| actions.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn held_at(&self, tick: u32) -> &[Action] {
+        self.held.range(..=tick).next_back().map(|(_, held)| held.as_slice()).unwrap_or(&[])
+    }
+}
+
+pub struct Replay {
+    /// The game in its starting state.
+    pub game: Game,
+    pub ticks: u32,
+    pub actions: Actions,
+}
+
+impl Replay {
+    pub fn load(file_name: &str) -> save::Result<Replay> {
+        let fields = Fields::parse(&read_from_file(file_name)?)?;
+        let version = fields.parse_value::<u32>("replay")?;
+        if version == 0 || version > REPLAY_VERSION {
+            return Err(save::Error::Corrupted(format!("unsupported replay version {}", version)));
+        }
+        let mut game = save::from_save(&fields, None)?;
+        game.timing = Timing {
+            are: Duration::from_millis(fields.parse_value("are")?),
+            line_clear_delay: Duration::from_millis(fields.parse_value("line_clear_delay")?),
+            speed: if version == 1 { 100 } else { fields.parse_value("speed")? },
+        };
+        if !(MIN_SPEED..=MAX_SPEED).contains(&game.timing.speed) {
+            return Err("invalid speed".into());
+        }
+        let held = if version < 3 { Vec::new() } else { parse_actions(fields.get("held")?)? };
+        Ok(Replay {
+            game,
+            ticks: fields.parse_value("ticks")?,
+            actions: Actions {
+                pressed: parse_actions(fields.get("actions")?)?.into_iter().collect(),
+                held: held.into_iter().collect(),
+            },
+        })
+    }
+}
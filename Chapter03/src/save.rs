@@ -0,0 +1,187 @@
+// A game is saved when the player quits so that they can continue it later. The
+// save file is made of "key: value" lines, its first line giving the version of
+// the format:
+//
+//     version: 8
+//     mode: dig 10 false 0
+//     score: 1250
+//     ...
+//
+// Any change in what gets written has to bump `SAVE_VERSION`.
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::result;
+use std::str::FromStr;
+use std::time::Duration;
+
+use self::Error::*;
+use garbage::GARBAGE;
+use game::Game;
+use mode::as_millis;
+use pieces::PieceSet;
+use tetris::Tetris;
+use {read_from_file, write_into_file};
+
+pub const SAVE_FILE: &str = "savegame.txt";
+const SAVE_VERSION: u32 = 8;
+
+#[derive(Debug)]
+pub enum Error {
+    Corrupted(String),
+    Incompatible(u32),
+    Io(io::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            Corrupted(ref msg) => write!(formatter, "corrupted save: {}", msg),
+            Incompatible(version) => write!(formatter, "save made by an incompatible version of \
+                                                        the game (format {}, expected {})",
+                                            version, SAVE_VERSION),
+            Io(ref error) => error.fmt(formatter),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Io(ref error) => Some(error),
+            Corrupted(_) | Incompatible(_) => None,
+        }
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Io(error)
+    }
+}
+
+impl<'a> From<&'a str> for Error {
+    fn from(message: &'a str) -> Self {
+        Corrupted(message.to_string())
+    }
+}
+
+/// The "key: value" lines of a save file.
+pub struct Fields {
+    values: HashMap<String, String>,
+}
+
+impl Fields {
+    pub fn parse(content: &str) -> Result<Fields> {
+        let mut values = HashMap::new();
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            match line.find(':') {
+                Some(pos) => {
+                    values.insert(line[..pos].trim().to_string(), line[pos + 1..].trim().to_string());
+                }
+                None => return Err(Corrupted(format!("invalid line \"{}\"", line))),
+            }
+        }
+        Ok(Fields { values })
+    }
+
+    pub fn get(&self, key: &str) -> Result<&str> {
+        self.values.get(key)
+                   .map(|value| value.as_str())
+                   .ok_or_else(|| Corrupted(format!("missing \"{}\"", key)))
+    }
+
+    pub fn parse_value<T: FromStr>(&self, key: &str) -> Result<T> {
+        parse(self.get(key)?, key)
+    }
+}
+
+/// Parses `value`, `what` being used in the error message.
+pub fn parse<T: FromStr>(value: &str, what: &str) -> Result<T> {
+    value.parse().map_err(|_| Corrupted(format!("invalid {} \"{}\"", what, value)))
+}
+
+/// Parses space-separated values, of which there has to be exactly `nb` if
+/// it's given.
+pub fn parse_list<T: FromStr>(value: &str, nb: Option<usize>, what: &str) -> Result<Vec<T>> {
+    let values = value.split_whitespace().map(|v| parse(v, what)).collect::<Result<Vec<T>>>()?;
+    match nb {
+        Some(nb) if values.len() != nb => {
+            Err(Corrupted(format!("expected {} values for {}, got {}", nb, what, values.len())))
+        }
+        _ => Ok(values),
+    }
+}
+
+/// Map cells are saved as a single character: '.' when empty, 'X' for garbage
+/// and the base 36 piece id otherwise.
+pub fn cell_to_char(cell: u8) -> char {
+    match cell {
+        0 => '.',
+        GARBAGE => 'X',
+        _ => ::std::char::from_digit(cell as u32 - 1, 36).expect("too many pieces"),
+    }
+}
+
+pub fn char_to_cell(c: char) -> Option<u8> {
+    match c {
+        '.' => Some(0),
+        'X' => Some(GARBAGE),
+        _ => c.to_digit(36).map(|id| id as u8 + 1),
+    }
+}
+
+/// Formats the whole game as the lines of a save file.
+pub fn to_save(game: &Game) -> String {
+    format!("version: {}\ntime: {}\nfall_timer: {}\ngarbage_timer: {}\n\
+             spawn_delay: {}\nclearing: {}\nranked: {}\n{}",
+            SAVE_VERSION, as_millis(game.time), as_millis(game.fall_timer),
+            as_millis(game.garbage_timer), as_millis(game.spawn_delay),
+            game.clearing, game.ranked, game.tetris.to_save())
+}
+
+/// Rebuilds a game from the fields written by `to_save`, with the piece set
+/// they name or with `piece_set`.
+pub fn from_save(fields: &Fields, piece_set: Option<PieceSet>) -> Result<Game> {
+    let version = fields.parse_value::<u32>("version")?;
+    if version != SAVE_VERSION {
+        return Err(Incompatible(version));
+    }
+    let duration = |key| fields.parse_value(key).map(Duration::from_millis);
+    let mut game = Game::new(Tetris::from_save(fields, piece_set)?);
+    game.time = duration("time")?;
+    game.fall_timer = duration("fall_timer")?;
+    game.garbage_timer = duration("garbage_timer")?;
+    game.spawn_delay = duration("spawn_delay")?;
+    game.clearing = fields.parse_value("clearing")?;
+    game.ranked = fields.parse_value("ranked")?;
+    Ok(game)
+}
+
+pub fn save(game: &Game) -> io::Result<()> {
+    write_into_file(&to_save(game), SAVE_FILE)
+}
+
+pub fn exists() -> bool {
+    fs::metadata(SAVE_FILE).is_ok()
+}
+
+/// Loads the saved game. The save file is removed once loaded so that a game
+/// can't be continued twice.
+pub fn load() -> Result<Game> {
+    let game = from_save(&Fields::parse(&read_from_file(SAVE_FILE)?)?, None)?;
+    fs::remove_file(SAVE_FILE)?;
+    Ok(game)
+}
+
+/// Moves an unreadable save out of the way so that it isn't offered again.
+pub fn discard() -> io::Result<String> {
+    let backup = format!("{}.bad", SAVE_FILE);
+    fs::rename(SAVE_FILE, &backup)?;
+    Ok(backup)
+}
@@ -0,0 +1,347 @@
+// New game modes don't need to be compiled in: mods are Rhai scripts of the
+// "mods" directory, played with `--mod NAME`. The game calls the functions the
+// script defines among these ones:
+//
+//     on_start()                       before the first piece
+//     on_tick()                        every tick, 60 times per second
+//     on_spawn(piece)                  a piece appeared, `piece` being its name
+//     on_lock()                        the current piece got locked
+//     on_lines_cleared(lines, tspin, perfect_clear)
+//     on_level_up(level)
+//     on_game_over(reason)
+//
+// binding `this` to an object map which the mod can keep its own data in for
+// the whole game. Scripts look at the game and change it with:
+//
+//     width(), height()                size of the visible map
+//     cell(x, y)                       cell of the map, without the current piece
+//     set_cell(x, y, value)            0 empties the cell
+//     add_garbage(lines)
+//     set_gravity(ms), reset_gravity() time a piece takes to fall one line
+//     win(), lose()
+//     score(), lines(), level(), pieces(), time()
+//
+// Coordinates count from the top-left corner of the visible map and cells are
+// valued as everywhere else in the engine. What the script sees is updated
+// between ticks, and its changes are applied at the end of the tick.
+//
+// Scripts can only reach the game through these functions: Rhai itself has no
+// access to files, `import` is disabled, printing is ignored, and a script
+// running for too long or using too much memory is stopped.
+
+use std::cell::RefCell;
+use std::fs;
+use std::mem;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, FuncArgs, Map, Scope, AST, INT};
+
+use board::VANISH_ZONE;
+use game::Game;
+use garbage::GARBAGE;
+use mode::{as_millis, GameMode, GameOver, Outcome};
+use observer::GameObserver;
+use pieces::PieceSet;
+use read_from_file;
+use tetrimino::Tetrimino;
+use tetris::Tetris;
+
+pub const MODS_DIRECTORY: &str = "mods";
+const EXTENSION: &str = "rhai";
+
+/// Limits keeping a script from freezing the game or taking all the memory. The
+/// operations are counted for every function call.
+const MAX_OPERATIONS: u64 = 100_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_SIZE: usize = 10_000;
+/// Slowest gravity a script can set, in milliseconds.
+const MAX_GRAVITY: INT = 60_000;
+
+/// A change asked by the script, applied at the end of the tick.
+enum Command {
+    SetCell(usize, usize, u8),
+    AddGarbage(u32),
+    SetGravity(Option<u32>),
+}
+
+/// What the script knows about the game and what it asked for.
+#[derive(Default)]
+struct World {
+    /// Visible lines of the map.
+    board: Vec<Vec<u8>>,
+    nb_piece_kinds: usize,
+    score: INT,
+    lines: INT,
+    level: INT,
+    pieces: INT,
+    /// Time played, in milliseconds.
+    time: INT,
+    commands: Vec<Command>,
+    outcome: Option<Outcome>,
+}
+
+impl World {
+    fn update(&mut self, tetris: &Tetris, time: Duration) {
+        self.board = tetris.game_map.lines()[VANISH_ZONE..].to_vec();
+        self.nb_piece_kinds = tetris.piece_set.len();
+        self.score = tetris.score as INT;
+        self.lines = tetris.nb_lines as INT;
+        self.level = tetris.current_level as INT;
+        self.pieces = tetris.nb_pieces as INT;
+        self.time = as_millis(time) as INT;
+    }
+
+    fn width(&self) -> INT {
+        self.board.first().map_or(0, |line| line.len()) as INT
+    }
+
+    fn height(&self) -> INT {
+        self.board.len() as INT
+    }
+
+    /// Checks that (`x`, `y`) is in the map and returns it as indexes.
+    fn position(&self, x: INT, y: INT) -> Result<(usize, usize), Box<EvalAltResult>> {
+        if x < 0 || y < 0 || x >= self.width() || y >= self.height() {
+            return Err(format!("cell ({}, {}) is out of the map", x, y).into());
+        }
+        Ok((x as usize, y as usize))
+    }
+}
+
+/// The compiled script and its data.
+struct Runtime {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    /// The mod's own data, bound to `this`.
+    state: Dynamic,
+    /// What stopped the script, if it failed.
+    error: Option<String>,
+}
+
+impl Runtime {
+    /// Calls the function `name` of the script if it has one.
+    fn call(&mut self, name: &str, args: impl FuncArgs) {
+        if self.error.is_some() || !self.ast.iter_functions().any(|function| function.name == name) {
+            return
+        }
+        // The top-level statements were run when loading the script, they must
+        // not be run again with every call.
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.state);
+        let result = self.engine.call_fn_with_options::<Dynamic>(options, &mut self.scope, &self.ast,
+                                                                  name, args);
+        if let Err(e) = result {
+            self.error = Some(format!("{}: {}", name, e));
+        }
+    }
+}
+
+/// Calls the script when the engine notifies it.
+struct Hooks {
+    runtime: Rc<RefCell<Runtime>>,
+    /// Names of the pieces, given to `on_spawn`.
+    names: Vec<String>,
+}
+
+impl GameObserver for Hooks {
+    fn on_spawn(&mut self, piece: &Tetrimino) {
+        let name = self.names[piece.id as usize].clone();
+        self.runtime.borrow_mut().call("on_spawn", (name,));
+    }
+
+    fn on_lock(&mut self, _piece: &Tetrimino) {
+        self.runtime.borrow_mut().call("on_lock", ());
+    }
+
+    fn on_lines_cleared(&mut self, rows: &[usize], tspin: bool, perfect_clear: bool) {
+        self.runtime.borrow_mut().call("on_lines_cleared", (rows.len() as INT, tspin, perfect_clear));
+    }
+
+    fn on_level_up(&mut self, level: u32) {
+        self.runtime.borrow_mut().call("on_level_up", (level as INT,));
+    }
+
+    fn on_game_over(&mut self, reason: GameOver) {
+        self.runtime.borrow_mut().call("on_game_over", (reason.description().to_string(),));
+    }
+}
+
+/// A script function returning something about the game.
+fn getter(world: &Rc<RefCell<World>>, get: fn(&World) -> INT) -> impl Fn() -> INT {
+    let world = world.clone();
+    move || get(&world.borrow())
+}
+
+/// An engine running scripts with nothing but the functions of the game.
+fn new_engine(world: &Rc<RefCell<World>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.on_print(|_| {});
+    engine.on_debug(|_, _, _| {});
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.set_max_string_size(MAX_SIZE);
+    engine.set_max_array_size(MAX_SIZE);
+    engine.set_max_map_size(MAX_SIZE);
+
+    engine.register_fn("width", getter(world, World::width));
+    engine.register_fn("height", getter(world, World::height));
+    engine.register_fn("score", getter(world, |world| world.score));
+    engine.register_fn("lines", getter(world, |world| world.lines));
+    engine.register_fn("level", getter(world, |world| world.level));
+    engine.register_fn("pieces", getter(world, |world| world.pieces));
+    engine.register_fn("time", getter(world, |world| world.time));
+
+    let w = world.clone();
+    engine.register_fn("cell", move |x: INT, y: INT| -> Result<INT, Box<EvalAltResult>> {
+        let world = w.borrow();
+        let (x, y) = world.position(x, y)?;
+        Ok(world.board[y][x] as INT)
+    });
+    let w = world.clone();
+    engine.register_fn("set_cell", move |x: INT, y: INT, value: INT| -> Result<(), Box<EvalAltResult>> {
+        let mut world = w.borrow_mut();
+        let (x, y) = world.position(x, y)?;
+        if value < 0 || (value as usize > world.nb_piece_kinds && value != GARBAGE as INT) {
+            return Err(format!("invalid cell value {}", value).into());
+        }
+        world.commands.push(Command::SetCell(x, y, value as u8));
+        Ok(())
+    });
+    let w = world.clone();
+    engine.register_fn("add_garbage", move |lines: INT| -> Result<(), Box<EvalAltResult>> {
+        let mut world = w.borrow_mut();
+        if !(0..=world.height()).contains(&lines) {
+            return Err(format!("invalid number of garbage lines {}", lines).into());
+        }
+        world.commands.push(Command::AddGarbage(lines as u32));
+        Ok(())
+    });
+    let w = world.clone();
+    engine.register_fn("set_gravity", move |delay: INT| -> Result<(), Box<EvalAltResult>> {
+        if !(1..=MAX_GRAVITY).contains(&delay) {
+            return Err(format!("gravity has to be between 1 and {} milliseconds", MAX_GRAVITY).into());
+        }
+        w.borrow_mut().commands.push(Command::SetGravity(Some(delay as u32)));
+        Ok(())
+    });
+    let w = world.clone();
+    engine.register_fn("reset_gravity", move || w.borrow_mut().commands.push(Command::SetGravity(None)));
+    let w = world.clone();
+    engine.register_fn("win", move || {
+        w.borrow_mut().outcome.get_or_insert(Outcome::Won);
+    });
+    let w = world.clone();
+    engine.register_fn("lose", move || {
+        w.borrow_mut().outcome.get_or_insert(Outcome::Lost(GameOver::ModRule));
+    });
+    engine
+}
+
+/// The script of a mod, driving a game once it's started.
+pub struct Script {
+    runtime: Rc<RefCell<Runtime>>,
+    world: Rc<RefCell<World>>,
+}
+
+impl Script {
+    /// Loads "mods/`name`.rhai" and runs its top-level statements.
+    pub fn load(name: &str) -> Result<Script, String> {
+        // Names can't lead out of the directory.
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("invalid mod name \"{}\"", name));
+        }
+        let file_name = Path::new(MODS_DIRECTORY).join(format!("{}.{}", name, EXTENSION));
+        let file_name = file_name.to_string_lossy();
+        let source = read_from_file(&file_name).map_err(|e| format!("\"{}\": {}", file_name, e))?;
+
+        let world = Rc::new(RefCell::new(World::default()));
+        let engine = new_engine(&world);
+        let ast = engine.compile(&source).map_err(|e| format!("\"{}\": {}", file_name, e))?;
+        let mut scope = Scope::new();
+        engine.run_ast_with_scope(&mut scope, &ast).map_err(|e| format!("\"{}\": {}", file_name, e))?;
+        let runtime = Runtime {
+            engine,
+            ast,
+            scope,
+            state: Dynamic::from_map(Map::new()),
+            error: None,
+        };
+        Ok(Script {
+            runtime: Rc::new(RefCell::new(runtime)),
+            world,
+        })
+    }
+
+    /// Names of the mods of the "mods" directory.
+    pub fn list() -> Vec<String> {
+        let mut names = fs::read_dir(MODS_DIRECTORY).map(|entries| {
+            entries.filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != EXTENSION {
+                    return None;
+                }
+                Some(path.file_stem()?.to_string_lossy().into_owned())
+            }).collect::<Vec<_>>()
+        }).unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    /// Starts a game of the mod.
+    pub fn start(self, piece_set: PieceSet) -> Game {
+        let mut game = Game::new(Tetris::new(GameMode::Mod, piece_set));
+        let names = game.tetris.piece_set.pieces.iter().map(|piece| piece.name.clone()).collect();
+        game.tetris.add_observer(Box::new(Hooks {
+            runtime: self.runtime.clone(),
+            names,
+        }));
+        self.world.borrow_mut().update(&game.tetris, game.time);
+        self.runtime.borrow_mut().call("on_start", ());
+        self.apply(&mut game.tetris, game.time);
+        game.script = Some(self);
+        game
+    }
+
+    /// Runs `on_tick` and applies the changes asked since the last tick. Returns
+    /// the outcome of the game if the script ended it.
+    pub fn tick(&mut self, tetris: &mut Tetris, time: Duration) -> Option<Outcome> {
+        self.world.borrow_mut().update(tetris, time);
+        self.runtime.borrow_mut().call("on_tick", ());
+        self.apply(tetris, time);
+        self.world.borrow().outcome
+    }
+
+    /// What stopped the script, which then doesn't do anything anymore.
+    pub fn error(&self) -> Option<String> {
+        self.runtime.borrow().error.clone()
+    }
+
+    fn apply(&self, tetris: &mut Tetris, time: Duration) {
+        let commands = mem::take(&mut self.world.borrow_mut().commands);
+        for command in commands {
+            match command {
+                Command::SetCell(x, y, cell) => {
+                    let y = y + VANISH_ZONE;
+                    let previous = tetris.game_map.lines()[y][x];
+                    tetris.game_map.set(x, y, cell);
+                    // The cells of the current piece stay as they are.
+                    if let Some(ref piece) = tetris.current_piece {
+                        if !piece.test_current_position(&tetris.game_map) {
+                            tetris.game_map.set(x, y, previous);
+                        }
+                    }
+                }
+                Command::AddGarbage(lines) => {
+                    tetris.add_garbage(lines);
+                }
+                Command::SetGravity(delay) => tetris.gravity = delay,
+            }
+        }
+        // The hooks called during the next tick see the changes.
+        self.world.borrow_mut().update(tetris, time);
+    }
+}
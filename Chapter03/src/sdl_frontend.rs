@@ -0,0 +1,820 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use sdl2;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::image::{self, LoadTexture, INIT_PNG};
+use sdl2::keyboard::{Keycode, Scancode};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas, RenderTarget, Texture, TextureCreator};
+use sdl2::surface::{Surface, SurfaceRef};
+use sdl2::video::{FullscreenType, Window};
+use sdl2::EventPump;
+
+use encoder::{self, Animation, Apng, Gif, Image};
+use font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+use tetris::accessibility::{cell_letter, text_color, ColorScheme};
+use tetris::animation::{Animations, CellEffect};
+use tetris::board::{self, VANISH_ZONE, VISIBLE_HEIGHT};
+use tetris::garbage::GARBAGE;
+use tetris::mode::as_millis;
+use tetris::replay::Replay;
+use tetris::settings::{self, Settings};
+use tetris::{Action, GameResult, PieceSet, TICK};
+use theme::{self, Rgb, Theme};
+use spectator::Viewer;
+use {play, watch as watch_game, Frame, Frontend, GameOptions};
+
+/// Size of the window when it opens, and of the exported replays.
+const WIDTH: u32 = 1040;
+const HEIGHT: u32 = 720;
+/// Size of the textures filling the cells without a tile, stretched to the
+/// size of the cells.
+const TEXTURE_SIZE: u32 = 40;
+/// Widths in cells of the hold panel, left of the map, and of the one with the
+/// preview and the HUD, right of it. They include the gap to the map.
+const HOLD_WIDTH: u32 = 5;
+const SIDE_WIDTH: u32 = 9;
+/// Room left around everything, in cells.
+const MARGIN: u32 = 1;
+
+/// Where everything goes, computed from the size of what's drawn on. For a
+/// window, that's its size in pixels rather than in points, so that the game
+/// fills high-DPI screens.
+#[derive(Clone, Copy, Debug)]
+struct Layout {
+    /// Size of a cell in pixels.
+    cell: u32,
+    /// Size of a pixel of the font.
+    text_scale: u32,
+    /// Thickness of the borders of the map and of the panels.
+    border: u32,
+    /// Top-left corner of the map.
+    grid_x: i32,
+    grid_y: i32,
+    /// Where the held piece and the next one are drawn, under their label.
+    hold: Rect,
+    preview: Rect,
+    /// Top-left corner of the HUD.
+    hud_x: i32,
+    hud_y: i32,
+}
+
+impl Layout {
+    fn new((width, height): (u32, u32)) -> Layout {
+        let columns = MARGIN + HOLD_WIDTH + board::WIDTH as u32 + SIDE_WIDTH + MARGIN;
+        let rows = MARGIN + VISIBLE_HEIGHT as u32 + MARGIN;
+        let cell = (width / columns).min(height / rows).max(1);
+        let text_scale = (cell / 20).max(1);
+        let border = (cell / 4).max(1);
+        // The whole is centred, whatever the shape of the window.
+        let left = (width.saturating_sub(columns * cell) / 2 + MARGIN * cell) as i32;
+        let top = (height.saturating_sub(rows * cell) / 2 + MARGIN * cell) as i32;
+        let grid_x = left + (HOLD_WIDTH * cell) as i32;
+        let right = grid_x + ((board::WIDTH as u32 + 1) * cell) as i32;
+        // The panels fit any tetrimino.
+        let panel_y = top + ((GLYPH_HEIGHT + 3) * text_scale + border) as i32;
+        let hold = Rect::new(left + border as i32, panel_y, 4 * cell, 4 * cell);
+        let preview = Rect::new(right + border as i32, panel_y, 4 * cell, 4 * cell);
+        Layout {
+            cell,
+            text_scale,
+            border,
+            grid_x,
+            grid_y: top,
+            hold,
+            preview,
+            hud_x: right,
+            hud_y: preview.bottom() + (border + cell) as i32,
+        }
+    }
+
+    fn line_height(&self) -> i32 {
+        ((GLYPH_HEIGHT + 3) * self.text_scale) as i32
+    }
+}
+
+fn create_texture_rect<'a, T: RenderTarget>(canvas: &mut Canvas<T>,
+                                            texture_creator: &'a TextureCreator<T::Context>,
+                                            r: u8, g: u8, b: u8,
+                                            width: u32, height: u32) -> Option<Texture<'a>> {
+    if let Ok(mut square_texture) =
+        texture_creator.create_texture_target(None, width, height) {
+        canvas.with_texture_canvas(&mut square_texture, |texture| {
+            texture.set_draw_color(Color::RGB(r, g, b));
+            texture.clear();
+        }).expect("Failed to color a texture");
+        Some(square_texture)
+    } else {
+        None
+    }
+}
+
+/// Loads an image of `theme`, if it has it. A broken image is reported and
+/// treated as missing.
+fn load_image<'a, C>(texture_creator: &'a TextureCreator<C>, theme: &Theme,
+                     file_name: &str) -> Option<Texture<'a>> {
+    let path = theme.image(file_name)?;
+    match texture_creator.load_texture(&path) {
+        Ok(texture) => Some(texture),
+        Err(e) => {
+            println!("Failed to load \"{}\": {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// The textures of a theme.
+struct Skin<'a> {
+    theme: Theme,
+    background: Option<Texture<'a>>,
+    grid: Texture<'a>,
+    border: Texture<'a>,
+    atlas: Option<Texture<'a>>,
+    /// Number of tiles of the atlas, and their size in pixels.
+    tiles: u32,
+    tile_size: u32,
+    // Cells without a tile are filled with the colours of the piece set, so
+    // these textures are created as needed.
+    textures: HashMap<Rgb, Texture<'a>>,
+}
+
+impl<'a> Skin<'a> {
+    fn load<T: RenderTarget>(canvas: &mut Canvas<T>,
+                             texture_creator: &'a TextureCreator<T::Context>,
+                             theme: Theme) -> Skin<'a> {
+        let palette = theme.palette.clone();
+        let grid = load_image(texture_creator, &theme, "grid.png").unwrap_or_else(|| {
+            create_texture_rect(canvas, texture_creator,
+                                palette.grid.0, palette.grid.1, palette.grid.2,
+                                TEXTURE_SIZE * 10, TEXTURE_SIZE * 16)
+                .expect("Failed to create a texture")
+        });
+        let border = load_image(texture_creator, &theme, "border.png").unwrap_or_else(|| {
+            create_texture_rect(canvas, texture_creator,
+                                palette.border.0, palette.border.1, palette.border.2,
+                                TEXTURE_SIZE * 10 + 20, TEXTURE_SIZE * 16 + 20)
+                .expect("Failed to create a texture")
+        });
+        let atlas = load_image(texture_creator, &theme, theme::ATLAS_FILE);
+        // The tiles are square, so the height of the atlas gives their size.
+        let (tiles, tile_size) = match atlas {
+            Some(ref atlas) => {
+                let query = atlas.query();
+                (query.width / query.height.max(1), query.height)
+            }
+            None => (0, 0),
+        };
+        Skin {
+            background: load_image(texture_creator, &theme, "background.png"),
+            theme,
+            grid,
+            border,
+            atlas,
+            tiles,
+            tile_size,
+            textures: HashMap::new(),
+        }
+    }
+}
+
+/// The entries of the options menu.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MenuItem {
+    Theme,
+    ColorScheme,
+    Patterns,
+    HighContrast,
+}
+
+const MENU_ITEMS: [MenuItem; 4] = [MenuItem::Theme, MenuItem::ColorScheme, MenuItem::Patterns,
+                                   MenuItem::HighContrast];
+
+/// Index of the item `step` items away from `current` among `len` of them,
+/// going round.
+fn cycle(len: usize, current: usize, step: isize) -> usize {
+    (current as isize + step).rem_euclid(len as isize) as usize
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value { "on" } else { "off" }
+}
+
+/// The options menu, opened with the O key. The game is paused while it's open.
+struct Menu {
+    selected: usize,
+    /// Shown under the entries, when changing an option went wrong.
+    message: Option<String>,
+}
+
+/// Draws the game on a window, or on a surface when exporting a replay.
+struct Renderer<'a, T: RenderTarget> {
+    canvas: Canvas<T>,
+    texture_creator: &'a TextureCreator<T::Context>,
+    skin: Skin<'a>,
+    /// Whether the letter of their piece is drawn on the cells.
+    patterns: bool,
+    layout: Layout,
+}
+
+impl<'a, T: RenderTarget> Renderer<'a, T> {
+    fn new(mut canvas: Canvas<T>, texture_creator: &'a TextureCreator<T::Context>,
+           theme: Theme, patterns: bool) -> Renderer<'a, T> {
+        // Used to draw the flashes and the menu over the game.
+        canvas.set_blend_mode(BlendMode::Blend);
+        let skin = Skin::load(&mut canvas, texture_creator, theme);
+        let layout = Layout::new(canvas.output_size().expect("Couldn't get the size of the canvas"));
+        Renderer {
+            canvas,
+            texture_creator,
+            skin,
+            patterns,
+            layout,
+        }
+    }
+
+    /// Lays the game out again, after the size of the window changed.
+    fn resize(&mut self) {
+        self.layout = Layout::new(self.canvas.output_size().expect("Couldn't get the size of the canvas"));
+    }
+
+    /// `offset` moves the cell by that many pixels. Cells of the vanish zone
+    /// aren't drawn.
+    fn draw_cell(&mut self, x: isize, y: usize, offset: (i32, i32), cell: u8, piece_set: &PieceSet) {
+        if y < VANISH_ZONE {
+            return
+        }
+        let y = y - VANISH_ZONE;
+        let size = self.layout.cell;
+        let target = Rect::new(self.layout.grid_x + x as i32 * size as i32 + offset.0,
+                               self.layout.grid_y + y as i32 * size as i32 + offset.1,
+                               size, size);
+        self.draw_tile(target, cell, piece_set);
+    }
+
+    /// Fills `target` with the look of `cell`.
+    fn draw_tile(&mut self, target: Rect, cell: u8, piece_set: &PieceSet) {
+        let id = cell as u32 - 1;
+        let color = self.skin.theme.palette.color(piece_set, cell);
+        match self.skin.atlas {
+            Some(ref atlas) if cell != GARBAGE && id < self.skin.tiles => {
+                let size = self.skin.tile_size;
+                self.canvas.copy(atlas, Rect::new((id * size) as i32, 0, size, size), target)
+                           .expect("Couldn't copy texture into window");
+            }
+            _ => {
+                if !self.skin.textures.contains_key(&color) {
+                    let texture = create_texture_rect(&mut self.canvas, self.texture_creator,
+                                                      color.0, color.1, color.2,
+                                                      TEXTURE_SIZE, TEXTURE_SIZE)
+                                      .expect("Failed to create a texture");
+                    self.skin.textures.insert(color, texture);
+                }
+                self.canvas.copy(&self.skin.textures[&color], None, target)
+                           .expect("Couldn't copy texture into window");
+            }
+        }
+        if self.skin.theme.high_contrast {
+            // Outlines tell the cells of a same colour apart.
+            self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+            for i in 0..2 {
+                self.canvas.draw_rect(Rect::new(target.x() + i, target.y() + i,
+                                                target.width() - 2 * i as u32,
+                                                target.height() - 2 * i as u32))
+                           .expect("Couldn't draw a cell outline");
+            }
+        }
+        if self.patterns {
+            let (r, g, b) = text_color(color);
+            let scale = self.layout.text_scale;
+            let x = target.x() + (target.width() as i32 - (GLYPH_WIDTH * scale) as i32) / 2;
+            let y = target.y() + (target.height() as i32 - (GLYPH_HEIGHT * scale) as i32) / 2;
+            self.draw_text(&cell_letter(piece_set, cell).to_string(), x, y, Color::RGB(r, g, b));
+        }
+    }
+
+    fn draw_text(&mut self, text: &str, x: i32, y: i32, color: Color) {
+        let scale = self.layout.text_scale;
+        let mut rects = Vec::new();
+        for (i, c) in text.chars().enumerate() {
+            let char_x = x + (i as u32 * (GLYPH_WIDTH + 1) * scale) as i32;
+            for (row, bits) in font::glyph(c).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        rects.push(Rect::new(char_x + (column * scale) as i32,
+                                             y + (row as u32 * scale) as i32,
+                                             scale, scale));
+                    }
+                }
+            }
+        }
+        self.canvas.set_draw_color(color);
+        self.canvas.fill_rects(&rects).expect("Couldn't draw text");
+    }
+
+    /// Draws `area` like the map, with a border and `label` above it.
+    fn draw_panel(&mut self, label: &str, area: Rect, text: Color) {
+        let border = self.layout.border;
+        self.canvas.copy(&self.skin.border, None,
+                         Rect::new(area.x() - border as i32, area.y() - border as i32,
+                                   area.width() + 2 * border, area.height() + 2 * border))
+                   .expect("Couldn't copy texture into window");
+        self.canvas.copy(&self.skin.grid, None, area).expect("Couldn't copy texture into window");
+        let y = area.y() - border as i32 - self.layout.line_height();
+        self.draw_text(label, area.x() - border as i32, y, text);
+    }
+
+    /// Draws the piece of id `id` centred in `area`, with smaller cells than the
+    /// ones of the map.
+    fn draw_piece(&mut self, id: u8, area: Rect, piece_set: &PieceSet) {
+        let piece = match piece_set.spawn(id) {
+            Some(piece) => piece,
+            None => return,
+        };
+        let mut cells = Vec::new();
+        for (y, line) in piece.states[0].iter().enumerate() {
+            for (x, &cell) in line.iter().enumerate() {
+                if cell != 0 {
+                    cells.push((x as i32, y as i32, cell));
+                }
+            }
+        }
+        let (min_x, max_x) = (cells.iter().map(|c| c.0).min(), cells.iter().map(|c| c.0).max());
+        let (min_y, max_y) = (cells.iter().map(|c| c.1).min(), cells.iter().map(|c| c.1).max());
+        let (min_x, max_x, min_y, max_y) = match (min_x, max_x, min_y, max_y) {
+            (Some(min_x), Some(max_x), Some(min_y), Some(max_y)) => (min_x, max_x, min_y, max_y),
+            _ => return,
+        };
+        let (columns, rows) = ((max_x - min_x + 1) as u32, (max_y - min_y + 1) as u32);
+        let size = (self.layout.cell * 4 / 5).min(area.width() / columns)
+                                              .min(area.height() / rows).max(1);
+        let x = area.x() + (area.width() - columns * size) as i32 / 2 - min_x * size as i32;
+        let y = area.y() + (area.height() - rows * size) as i32 / 2 - min_y * size as i32;
+        for (cell_x, cell_y, cell) in cells {
+            self.draw_tile(Rect::new(x + cell_x * size as i32, y + cell_y * size as i32, size, size),
+                           cell, piece_set);
+        }
+    }
+
+    /// Draws everything but the menu, without presenting it.
+    fn draw_game(&mut self, frame: &Frame) {
+        let tetris = frame.tetris;
+        let animations = frame.animations;
+        let palette = self.skin.theme.palette.clone();
+        let (r, g, b) = palette.background;
+        self.canvas.set_draw_color(Color::RGB(r, g, b));
+        self.canvas.clear();
+        if let Some(ref background) = self.skin.background {
+            self.canvas.copy(background, None, None).expect("Couldn't copy texture into window");
+        }
+
+        let layout = self.layout;
+        let size = layout.cell;
+        let (r, g, b) = palette.text;
+        let text = Color::RGB(r, g, b);
+        if tetris.mode.allows(Action::Hold) {
+            self.draw_panel("Hold", layout.hold, text);
+            if let Some(id) = tetris.held_piece {
+                self.draw_piece(id, layout.hold, &tetris.piece_set);
+            }
+        }
+        self.draw_panel("Next", layout.preview, text);
+        if let Some(id) = frame.preview {
+            self.draw_piece(id, layout.preview, &tetris.piece_set);
+        }
+
+        let (shake_x, shake_y) = animations.shake();
+        let shake = ((shake_x * size as f64) as i32, (shake_y * size as f64) as i32);
+
+        let width = tetris.game_map.width() as u32 * size;
+        let height = tetris.game_map.visible_height() as u32 * size;
+        let (grid_x, grid_y) = (layout.grid_x + shake.0, layout.grid_y + shake.1);
+        let border = layout.border;
+        self.canvas.copy(&self.skin.border, None,
+                         Rect::new(grid_x - border as i32, grid_y - border as i32,
+                                   width + 2 * border, height + 2 * border))
+                   .expect("Couldn't copy texture into window");
+        self.canvas.copy(&self.skin.grid, None, Rect::new(grid_x, grid_y, width, height))
+                   .expect("Couldn't copy texture into window");
+
+        let board = animations.board().unwrap_or(tetris.game_map.lines());
+        for (y, line) in board.iter().enumerate().skip(VANISH_ZONE) {
+            for (x, &cell) in line.iter().enumerate() {
+                if cell == 0 {
+                    continue
+                }
+                match animations.cell_effect(x, y) {
+                    CellEffect::Normal => self.draw_cell(x as isize, y, shake, cell, &tetris.piece_set),
+                    CellEffect::Flash(amount) => {
+                        // Tiles can't be recoloured, so white is blended over them.
+                        self.draw_cell(x as isize, y, shake, cell, &tetris.piece_set);
+                        self.canvas.set_draw_color(Color::RGBA(255, 255, 255, (amount * 255.) as u8));
+                        let y = y - VANISH_ZONE;
+                        self.canvas.fill_rect(Rect::new(grid_x + x as i32 * size as i32,
+                                                        grid_y + y as i32 * size as i32,
+                                                        size, size))
+                                   .expect("Couldn't draw a flashing cell");
+                    }
+                    CellEffect::Hidden => {}
+                }
+            }
+        }
+        if let Some(ref piece) = tetris.current_piece {
+            // The piece slides smoothly towards the next line, unless it can't fall.
+            let offset = if piece.test_position(&tetris.game_map, piece.current_state as usize,
+                                                piece.x, piece.y + 1) {
+                (frame.interpolation * size as f64) as i32
+            } else {
+                0
+            };
+            let offset = (shake.0, shake.1 + offset);
+            for (shift_y, line) in piece.states[piece.current_state as usize].iter().enumerate() {
+                for (shift_x, &cell) in line.iter().enumerate() {
+                    if cell != 0 {
+                        self.draw_cell(piece.x + shift_x as isize, piece.y + shift_y, offset,
+                                       cell, &tetris.piece_set);
+                    }
+                }
+            }
+        }
+        // Particles shrink as they fade out.
+        for particle in animations.particles() {
+            let y = particle.y - VANISH_ZONE as f64;
+            if y < 0. {
+                continue
+            }
+            let particle_size = (size as f64 / 4. * particle.fade()) as u32;
+            let (r, g, b) = palette.color(&tetris.piece_set, particle.color);
+            self.canvas.set_draw_color(Color::RGB(r, g, b));
+            self.canvas.fill_rect(Rect::new(grid_x + (particle.x * size as f64) as i32,
+                                            grid_y + (y * size as f64) as i32,
+                                            particle_size.max(1), particle_size.max(1)))
+                       .expect("Couldn't draw particle");
+        }
+        // The HUD goes under the preview, one element per line.
+        for (i, &(label, ref value)) in frame.hud.iter().enumerate() {
+            let line = format!("{}: {}", label, value);
+            self.draw_text(&line, layout.hud_x, layout.hud_y + i as i32 * layout.line_height(), text);
+        }
+    }
+}
+
+/// Copies the pixels of an RGB24 surface, whose lines may be padded.
+fn surface_image(surface: &SurfaceRef) -> Image {
+    let (width, height) = surface.size();
+    let pitch = surface.pitch() as usize;
+    let pixels = surface.with_lock(|data| {
+        data.chunks(pitch).take(height as usize)
+            .flat_map(|line| line[..width as usize * 3].iter().cloned())
+            .collect()
+    });
+    Image { width, height, pixels }
+}
+
+/// Saves what's on the window, under a name made unique by the time in
+/// milliseconds, and by a number if a file already has it.
+fn save_screenshot(canvas: &Canvas<Window>) -> Result<String, String> {
+    let (width, height) = canvas.output_size()?;
+    let pixels = canvas.read_pixels(None, PixelFormatEnum::RGB24)?;
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let mut file_name = format!("screenshot_{}.png", time);
+    let mut nb = 1;
+    while Path::new(&file_name).exists() {
+        nb += 1;
+        file_name = format!("screenshot_{}_{}.png", time, nb);
+    }
+    encoder::write_png(&Image { width, height, pixels }, &file_name).map_err(|e| e.to_string())?;
+    Ok(file_name)
+}
+
+struct SdlFrontend<'a> {
+    renderer: Renderer<'a, Window>,
+    /// The settings as changed by the menu.
+    settings: Settings,
+    event_pump: EventPump,
+    menu: Option<Menu>,
+    /// The P key was pressed, the next frame is saved once drawn.
+    screenshot: bool,
+}
+
+impl<'a> SdlFrontend<'a> {
+    /// Switches to the next (`step` = 1) or previous (`step` = -1) theme.
+    fn change_theme(&mut self, step: isize) {
+        let names = theme::list();
+        let current = names.iter().position(|name| *name == self.settings.theme).unwrap_or(0);
+        let name = names[cycle(names.len(), current, step)].clone();
+        self.change_setting("theme", &name);
+    }
+
+    /// Changes a setting from the menu, reloading the theme with it, and writes
+    /// it to the settings file.
+    fn change_setting(&mut self, key: &str, value: &str) {
+        let mut settings = self.settings.clone();
+        let message = match settings.set(key, value).and_then(|_| Theme::load(&settings.theme)) {
+            Ok(theme) => {
+                let renderer = &mut self.renderer;
+                renderer.skin = Skin::load(&mut renderer.canvas, renderer.texture_creator,
+                                           theme.accessible(&settings));
+                renderer.patterns = settings.patterns;
+                self.settings = settings;
+                settings::store(key, value).err().map(|e| format!("Failed to save the settings: {}", e))
+            }
+            Err(e) => Some(e),
+        };
+        if let Some(ref mut menu) = self.menu {
+            menu.message = message;
+        }
+    }
+
+    /// Handles a key pressed while the menu is open.
+    fn menu_key(&mut self, keycode: Keycode) {
+        let selected = match self.menu {
+            Some(ref mut menu) => {
+                match keycode {
+                    Keycode::Up => menu.selected = menu.selected.saturating_sub(1),
+                    Keycode::Down => menu.selected = (menu.selected + 1).min(MENU_ITEMS.len() - 1),
+                    _ => {}
+                }
+                MENU_ITEMS[menu.selected]
+            }
+            None => return,
+        };
+        let step = match keycode {
+            Keycode::Left => -1,
+            Keycode::Right => 1,
+            _ => 0,
+        };
+        match selected {
+            _ if keycode == Keycode::O || keycode == Keycode::Escape || keycode == Keycode::Return => {
+                self.menu = None;
+            }
+            _ if step == 0 => {}
+            MenuItem::Theme => self.change_theme(step),
+            MenuItem::ColorScheme => {
+                let schemes = ColorScheme::ALL;
+                let current = schemes.iter().position(|&scheme| scheme == self.settings.color_scheme)
+                                     .unwrap_or(0);
+                self.change_setting("color_scheme", schemes[cycle(schemes.len(), current, step)].name());
+            }
+            MenuItem::Patterns => {
+                let value = (!self.settings.patterns).to_string();
+                self.change_setting("patterns", &value);
+            }
+            MenuItem::HighContrast => {
+                let value = (!self.settings.high_contrast).to_string();
+                self.change_setting("high_contrast", &value);
+            }
+        }
+    }
+
+    /// Switches between full screen and a window, and remembers it in the
+    /// settings file.
+    fn toggle_fullscreen(&mut self) {
+        let fullscreen = !self.settings.fullscreen;
+        let mode = if fullscreen { FullscreenType::Desktop } else { FullscreenType::Off };
+        if let Err(e) = self.renderer.canvas.window_mut().set_fullscreen(mode) {
+            println!("Failed to switch to full screen: {}", e);
+            return
+        }
+        self.settings.fullscreen = fullscreen;
+        self.renderer.resize();
+        if let Err(e) = settings::store("fullscreen", &fullscreen.to_string()) {
+            println!("Failed to save the settings: {}", e);
+        }
+    }
+
+    fn draw_menu(&mut self) {
+        let (selected, message) = match self.menu {
+            Some(ref menu) => (menu.selected, menu.message.clone()),
+            None => return,
+        };
+        let renderer = &mut self.renderer;
+        renderer.canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
+        renderer.canvas.fill_rect(None).expect("Couldn't draw the menu");
+        let line_height = renderer.layout.line_height();
+        let text_color = renderer.skin.theme.palette.text;
+        let text_color = Color::RGB(text_color.0, text_color.1, text_color.2);
+        let mut lines = vec!["Options".to_string(), String::new()];
+        for (i, item) in MENU_ITEMS.iter().enumerate() {
+            let cursor = if i == selected { ">" } else { " " };
+            let value = match *item {
+                MenuItem::Theme => format!("Theme: < {} >", renderer.skin.theme.name),
+                MenuItem::ColorScheme => format!("Colours: < {} >", self.settings.color_scheme.name()),
+                MenuItem::Patterns => format!("Letters: < {} >", on_off(self.settings.patterns)),
+                MenuItem::HighContrast => {
+                    format!("High contrast: < {} >", on_off(self.settings.high_contrast))
+                }
+            };
+            lines.push(format!("{} {}", cursor, value));
+        }
+        lines.push(String::new());
+        if let Some(message) = message {
+            lines.push(message);
+        }
+        lines.push("O: resume".to_string());
+        let (x, y) = (renderer.layout.grid_x, renderer.layout.grid_y);
+        for (i, line) in lines.iter().enumerate() {
+            renderer.draw_text(line, x, y + i as i32 * line_height, text_color);
+        }
+    }
+}
+
+impl<'a> Frontend for SdlFrontend<'a> {
+    fn poll_actions(&mut self) -> Option<Vec<Action>> {
+        let mut actions = Vec::new();
+        let events = self.event_pump.poll_iter().collect::<Vec<_>>();
+        for event in events {
+            match event {
+                Event::Window { win_event: WindowEvent::SizeChanged(..), .. } => {
+                    self.renderer.resize();
+                    continue
+                }
+                Event::KeyDown { keycode: Some(Keycode::F11), .. } => {
+                    self.toggle_fullscreen();
+                    continue
+                }
+                _ => {}
+            }
+            if self.menu.is_some() {
+                match event {
+                    Event::Quit { .. } => return None,
+                    Event::KeyDown { keycode: Some(keycode), .. } => self.menu_key(keycode),
+                    _ => {}
+                }
+                continue
+            }
+            match event {
+                Event::KeyDown { keycode: Some(Keycode::O), .. } => {
+                    self.menu = Some(Menu { selected: 0, message: None });
+                }
+                Event::KeyDown { keycode: Some(Keycode::P), .. } => self.screenshot = true,
+                Event::Quit { .. } |
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return None,
+                Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
+                    actions.push(Action::SoftDrop);
+                }
+                Event::KeyDown { keycode: Some(Keycode::Right), .. } => {
+                    actions.push(Action::MoveRight);
+                }
+                Event::KeyDown { keycode: Some(Keycode::Left), .. } => {
+                    actions.push(Action::MoveLeft);
+                }
+                Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
+                    actions.push(Action::Rotate);
+                }
+                Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
+                    actions.push(Action::HardDrop);
+                }
+                Event::KeyDown { keycode: Some(Keycode::C), .. } |
+                Event::KeyDown { keycode: Some(Keycode::LShift), .. } => {
+                    actions.push(Action::Hold);
+                }
+                _ => {}
+            }
+        }
+        Some(actions)
+    }
+
+    fn held_actions(&self) -> Vec<Action> {
+        if self.menu.is_some() {
+            return Vec::new();
+        }
+        let keyboard = self.event_pump.keyboard_state();
+        let mut held = Vec::new();
+        if keyboard.is_scancode_pressed(Scancode::Up) {
+            held.push(Action::Rotate);
+        }
+        if keyboard.is_scancode_pressed(Scancode::C) || keyboard.is_scancode_pressed(Scancode::LShift) {
+            held.push(Action::Hold);
+        }
+        held
+    }
+
+    fn draw(&mut self, frame: &Frame) {
+        self.renderer.draw_game(frame);
+        self.draw_menu();
+        // The content of the window is undefined once presented.
+        if self.screenshot {
+            self.screenshot = false;
+            match save_screenshot(&self.renderer.canvas) {
+                Ok(file_name) => println!("Screenshot saved to \"{}\"", file_name),
+                Err(e) => println!("Failed to save the screenshot: {}", e),
+            }
+        }
+        self.renderer.canvas.present();
+    }
+
+    fn paused(&self) -> bool {
+        self.menu.is_some()
+    }
+}
+
+/// Loads the theme of the settings. A missing theme isn't worth giving up.
+fn settings_theme(settings: &Settings) -> Theme {
+    Theme::load(&settings.theme).unwrap_or_else(|e| {
+        println!("Failed to load theme: {}", e);
+        Theme::builtin()
+    }).accessible(settings)
+}
+
+pub fn run(options: GameOptions) -> GameResult {
+    let settings = options.settings.clone();
+    with_frontend(&settings, |frontend| play(frontend, options))
+}
+
+pub fn watch(viewer: Viewer, settings: &Settings) -> Result<(), String> {
+    with_frontend(settings, |frontend| watch_game(frontend, viewer, settings))
+}
+
+/// Opens the window and runs `f` with it.
+fn with_frontend<T, F: FnOnce(&mut SdlFrontend) -> T>(settings: &Settings, f: F) -> T {
+    let sdl_context = sdl2::init().expect("SDL initialization failed");
+    let video_subsystem = sdl_context.video().expect("Couldn't get SDL video subsystem");
+
+    let mut window = video_subsystem.window("Tetris", WIDTH, HEIGHT);
+    window.position_centered().resizable().allow_highdpi();
+    if settings.fullscreen {
+        window.fullscreen_desktop();
+    }
+    let window = window.build().expect("Failed to create window");
+    let mut builder = window.into_canvas().target_texture();
+    if settings.vsync {
+        builder = builder.present_vsync();
+    }
+    let canvas = builder.build().expect("Couldn't get window's canvas");
+    let texture_creator: TextureCreator<_> = canvas.texture_creator();
+    let _image_context = image::init(INIT_PNG).expect("Couldn't initialize image context");
+
+    let theme = settings_theme(settings);
+    let mut settings = settings.clone();
+    settings.theme = theme.name.clone();
+    let mut frontend = SdlFrontend {
+        renderer: Renderer::new(canvas, &texture_creator, theme, settings.patterns),
+        settings,
+        event_pump: sdl_context.event_pump().expect("Failed to get SDL event pump"),
+        menu: None,
+        screenshot: false,
+    };
+    f(&mut frontend)
+}
+
+/// Replays a game offscreen, with the software renderer, and saves it as an
+/// animated GIF or PNG depending on the extension of `output`. Frames are taken
+/// `fps` times per second of the game and resized by `scale`.
+pub fn export(replay_file: &str, output: &str, fps: u32, scale: f64,
+              settings: &Settings) -> Result<(), String> {
+    let Replay { mut game, ticks, actions } = Replay::load(replay_file).map_err(|e| e.to_string())?;
+    let mut encoder: Box<dyn Animation> = if output.to_lowercase().ends_with(".gif") {
+        Box::new(Gif::default())
+    } else {
+        Box::new(Apng::default())
+    };
+    let surface = Surface::new(WIDTH, HEIGHT, PixelFormatEnum::RGB888)?;
+    let canvas = surface.into_canvas()?;
+    let texture_creator = canvas.texture_creator();
+    let _image_context = image::init(INIT_PNG)?;
+    let mut renderer = Renderer::new(canvas, &texture_creator, settings_theme(settings),
+                                     settings.patterns);
+    let (width, height) = (((WIDTH as f64 * scale) as u32).max(1),
+                           ((HEIGHT as f64 * scale) as u32).max(1));
+    let mut scaled = Surface::new(width, height, PixelFormatEnum::RGB24)?;
+
+    let mut animations = Animations::new(settings.animations, settings.clear_animation,
+                                         settings.screen_shake);
+    let frame_time = Duration::from_secs(1) / fps.max(1);
+    // Time of the game since the last frame, the first one is taken right away.
+    let mut lag = frame_time;
+    let mut tick = 0;
+    let mut over = false;
+    loop {
+        over |= tick >= ticks;
+        // The end of the game always gets a frame.
+        if lag >= frame_time || over {
+            renderer.draw_game(&Frame {
+                tetris: &game.tetris,
+                hud: &game.hud(),
+                interpolation: if settings.interpolation { game.fall_progress() } else { 0. },
+                preview: game.tetris.next_preview(),
+                animations: &animations,
+            });
+            renderer.canvas.present();
+            renderer.canvas.surface().blit_scaled(None, &mut scaled, None)?;
+            encoder.add_frame(surface_image(&scaled), as_millis(frame_time));
+            lag = lag.checked_sub(frame_time).unwrap_or_default();
+        }
+        if over {
+            break
+        }
+        game.set_held(actions.held_at(tick));
+        over = game.tick(actions.at(tick), None).is_some();
+        for event in game.take_events() {
+            animations.handle(&event);
+        }
+        animations.update(TICK, game.clear_progress());
+        tick += 1;
+        lag += TICK;
+    }
+    encoder.write(output).map_err(|e| format!("Failed to write \"{}\": {}", output, e))
+}
@@ -0,0 +1,173 @@
+// The player's preferences are read from "settings.txt", made of "key = value"
+// lines:
+//
+//     # Frames drawn per second, 0 to draw as often as possible.
+//     fps_cap = 60
+//     vsync = true
+//     # Start the SDL version in full screen, F11 switches it.
+//     fullscreen = false
+//     # Draw the falling piece between two lines.
+//     interpolation = true
+//     # Delays in milliseconds after a piece gets locked, and after it clears
+//     # lines. They're part of the gameplay, the animations only fill them.
+//     are = 0
+//     line_clear_delay = 300
+//     # Speed of the falling pieces, in percent of their normal speed. Games
+//     # played with other delays or speed than these don't get ranked.
+//     game_speed = 100
+//     # Set to false to turn all the effects off.
+//     animations = true
+//     # flash, dissolve or particles
+//     clear_animation = flash
+//     screen_shake = true
+//     # Theme of the SDL version, from the "themes" directory.
+//     theme = default
+//     # standard, red_green or blue_yellow (colours told apart with colour
+//     # blindness)
+//     color_scheme = standard
+//     # Draw the letter of their piece on the cells.
+//     patterns = false
+//     high_contrast = false
+//     # Name under which the daily challenges are ranked.
+//     profile = player
+//
+// Missing keys keep their default value. The options menu of the SDL version
+// (O key) and its F11 key write the options they change back to the file.
+
+use std::io;
+use std::time::Duration;
+
+use accessibility::ColorScheme;
+use animation::ClearStyle;
+use daily::{self, MAX_PROFILE_LEN};
+use game::{Timing, MAX_SPEED, MIN_SPEED};
+use {read_from_file, write_into_file};
+
+pub const SETTINGS_FILE: &str = "settings.txt";
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// 0 means no cap.
+    pub fps_cap: u32,
+    pub vsync: bool,
+    pub fullscreen: bool,
+    pub interpolation: bool,
+    pub timing: Timing,
+    pub animations: bool,
+    pub clear_animation: ClearStyle,
+    pub screen_shake: bool,
+    pub theme: String,
+    pub color_scheme: ColorScheme,
+    pub patterns: bool,
+    pub high_contrast: bool,
+    pub profile: String,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            fps_cap: 60,
+            vsync: true,
+            fullscreen: false,
+            interpolation: true,
+            timing: Timing::default(),
+            animations: true,
+            clear_animation: ClearStyle::Flash,
+            screen_shake: true,
+            theme: "default".to_string(),
+            color_scheme: ColorScheme::Standard,
+            patterns: false,
+            high_contrast: false,
+            profile: "player".to_string(),
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings file. Invalid lines are reported and ignored, the
+    /// game is still playable with the defaults.
+    pub fn load() -> Settings {
+        let mut settings = Settings::default();
+        let content = match read_from_file(SETTINGS_FILE) {
+            Ok(content) => content,
+            Err(_) => return settings,
+        };
+        for (nb, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let result = match line.find('=') {
+                Some(pos) => settings.set(line[..pos].trim(), line[pos + 1..].trim()),
+                None => Err(format!("expected \"key = value\", got \"{}\"", line)),
+            };
+            if let Err(e) = result {
+                println!("{}:{}: {}, line ignored", SETTINGS_FILE, nb + 1, e);
+            }
+        }
+        settings
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("invalid value \"{}\" for {}", value, key);
+        match key {
+            "fps_cap" => self.fps_cap = value.parse().map_err(|_| invalid())?,
+            "vsync" => self.vsync = value.parse().map_err(|_| invalid())?,
+            "fullscreen" => self.fullscreen = value.parse().map_err(|_| invalid())?,
+            "interpolation" => self.interpolation = value.parse().map_err(|_| invalid())?,
+            "are" => {
+                self.timing.are = Duration::from_millis(value.parse().map_err(|_| invalid())?);
+            }
+            "line_clear_delay" => {
+                self.timing.line_clear_delay =
+                    Duration::from_millis(value.parse().map_err(|_| invalid())?);
+            }
+            "game_speed" => {
+                let speed = value.parse().map_err(|_| invalid())?;
+                if !(MIN_SPEED..=MAX_SPEED).contains(&speed) {
+                    return Err(format!("game_speed has to be between {} and {}", MIN_SPEED, MAX_SPEED));
+                }
+                self.timing.speed = speed;
+            }
+            "animations" => self.animations = value.parse().map_err(|_| invalid())?,
+            "clear_animation" => {
+                self.clear_animation = ClearStyle::from_name(value).ok_or_else(invalid)?;
+            }
+            "screen_shake" => self.screen_shake = value.parse().map_err(|_| invalid())?,
+            "theme" => self.theme = value.to_string(),
+            "color_scheme" => {
+                self.color_scheme = ColorScheme::from_name(value).ok_or_else(invalid)?;
+            }
+            "patterns" => self.patterns = value.parse().map_err(|_| invalid())?,
+            "high_contrast" => self.high_contrast = value.parse().map_err(|_| invalid())?,
+            "profile" => {
+                if !daily::is_valid_profile(value) {
+                    return Err(format!("profiles have 1 to {} letters, digits, '_' or '-'",
+                                       MAX_PROFILE_LEN));
+                }
+                self.profile = value.to_string();
+            }
+            _ => return Err(format!("unknown setting \"{}\"", key)),
+        }
+        Ok(())
+    }
+}
+
+/// Writes `key = value` into the settings file, replacing the line of `key` if
+/// it has one so that the rest of the file is kept as it is.
+pub fn store(key: &str, value: &str) -> io::Result<()> {
+    let content = read_from_file(SETTINGS_FILE).unwrap_or_default();
+    let setting = format!("{} = {}", key, value);
+    let mut found = false;
+    let mut lines = content.lines().map(|line| {
+        let trimmed = line.trim();
+        let is_key = !trimmed.starts_with('#') &&
+                     trimmed.find('=').map(|pos| trimmed[..pos].trim() == key).unwrap_or(false);
+        found |= is_key;
+        if is_key { setting.clone() } else { line.to_string() }
+    }).collect::<Vec<_>>();
+    if !found {
+        lines.push(setting);
+    }
+    write_into_file(&(lines.join("\n") + "\n"), SETTINGS_FILE)
+}
@@ -0,0 +1,336 @@
+// Live games can be watched from other machines: with `--spectate ADDRESS`, the
+// game listens on ADDRESS and streams itself to every viewer connecting to it,
+// which `tetris watch ADDRESS` shows. The stream is made of text lines:
+//
+//     tetris-spectate 2         protocol version, sent first
+//     pieces my_pieces.txt      the piece set, as in its definition file, so
+//     # Lines starting...       that viewers don't need the file...
+//     end                       ...up to this line
+//     state                     the whole game, as in a save file...
+//     version: 8
+//     ...
+//     end                       ...up to this line
+//     time 12840 -              time played in milliseconds, and how far the
+//                               line-clear delay is (from 0 to 1) or '-'
+//     cells 3 19 2 4 19 2       changed cells of the map: x, y and value
+//     piece 2 4 3 0             current piece: id, x, y and rotation, or "none"
+//     hold 5                    held piece, or "none"
+//     score 120 4 1 12          score, lines, level and pieces
+//     clear 18,19 ....          cleared lines, then the map before their removal
+//     harddrop
+//     over lost lock out        "won", "lost" and the reason, or "quit"
+//
+// After the state, a viewer only gets what changed, once per frame of the game.
+// Coordinates count the lines of the vanish zone, as in save files. A line which
+// doesn't match the game, such as a piece out of the map, stops the viewer with
+// an error.
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use tetris::mode::as_millis;
+use tetris::save::{self, cell_to_char, char_to_cell, parse, Fields};
+use tetris::{Event, Game, Outcome, PieceSet};
+
+const PROTOCOL_VERSION: u32 = 2;
+/// A viewer taking longer than this to receive a frame is dropped rather than
+/// slowing the game down.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(50);
+
+fn format_map(lines: &[Vec<u8>]) -> String {
+    lines.iter()
+         .map(|line| line.iter().map(|&cell| cell_to_char(cell)).collect::<String>())
+         .collect::<Vec<_>>()
+         .join(" ")
+}
+
+fn format_piece(id: Option<u8>) -> String {
+    id.map(|id| id.to_string()).unwrap_or_else(|| "none".to_string())
+}
+
+/// Streams a game to the viewers connected to it.
+pub struct Server {
+    listener: TcpListener,
+    viewers: Vec<TcpStream>,
+    /// The map as the viewers last got it.
+    board: Vec<Vec<u8>>,
+    /// The last "piece", "hold" and "score" lines sent.
+    piece: String,
+    hold: String,
+    score: String,
+}
+
+impl Server {
+    pub fn bind(address: &str) -> io::Result<Server> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Server {
+            listener,
+            viewers: Vec::new(),
+            board: Vec::new(),
+            piece: String::new(),
+            hold: String::new(),
+            score: String::new(),
+        })
+    }
+
+    pub fn address(&self) -> String {
+        self.listener.local_addr().map(|address| address.to_string()).unwrap_or_default()
+    }
+
+    /// Sends to the viewers what changed since the last call, `events` being the
+    /// events of the game since then, and welcomes the new viewers.
+    pub fn broadcast(&mut self, game: &Game, events: &[Event]) {
+        let tetris = &game.tetris;
+        let progress = game.clear_progress().map(|p| format!("{:.3}", p)).unwrap_or_else(|| "-".to_string());
+        let mut message = format!("time {} {}\n", as_millis(game.time), progress);
+
+        let lines = tetris.game_map.lines();
+        let mut cells = Vec::new();
+        for (y, line) in lines.iter().enumerate() {
+            for (x, &cell) in line.iter().enumerate() {
+                if self.board.get(y).and_then(|line| line.get(x)) != Some(&cell) {
+                    cells.push(format!("{} {} {}", x, y, cell));
+                }
+            }
+        }
+        if !cells.is_empty() {
+            message.push_str(&format!("cells {}\n", cells.join(" ")));
+            self.board = lines.to_vec();
+        }
+        let piece = match tetris.current_piece {
+            Some(ref piece) => format!("piece {} {} {} {}\n", piece.id, piece.x, piece.y, piece.current_state),
+            None => "piece none\n".to_string(),
+        };
+        let hold = format!("hold {}\n", format_piece(tetris.held_piece));
+        let score = format!("score {} {} {} {}\n", tetris.score, tetris.nb_lines, tetris.current_level,
+                            tetris.nb_pieces);
+        for (line, last) in [(piece, &mut self.piece), (hold, &mut self.hold), (score, &mut self.score)] {
+            if line != *last {
+                message.push_str(&line);
+                *last = line;
+            }
+        }
+        for event in events {
+            match *event {
+                Event::Locked { ref rows, ref board } if !rows.is_empty() => {
+                    let rows = rows.iter().map(|y| y.to_string()).collect::<Vec<_>>();
+                    message.push_str(&format!("clear {} {}\n", rows.join(","), format_map(board)));
+                }
+                Event::Locked { .. } => {}
+                Event::HardDrop => message.push_str("harddrop\n"),
+            }
+        }
+        self.send(&message);
+
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => self.welcome(stream, game),
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    println!("Failed to accept a viewer: {}", e);
+                    break
+                }
+            }
+        }
+    }
+
+    /// Tells the viewers how the game ended.
+    pub fn finish(&mut self, outcome: Outcome) {
+        let outcome = match outcome {
+            Outcome::Won => "won".to_string(),
+            Outcome::Lost(reason) => format!("lost {}", reason.description()),
+            Outcome::Quit => "quit".to_string(),
+        };
+        self.send(&format!("over {}\n", outcome));
+    }
+
+    fn welcome(&mut self, mut stream: TcpStream, game: &Game) {
+        let piece_set = &game.tetris.piece_set;
+        let state = format!("tetris-spectate {}\npieces {}\n{}end\nstate\n{}end\n", PROTOCOL_VERSION,
+                            piece_set.source, piece_set.definition, save::to_save(game));
+        let setup = stream.set_nonblocking(false)
+                          .and_then(|_| stream.set_nodelay(true))
+                          .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)))
+                          .and_then(|_| stream.write_all(state.as_bytes()));
+        if setup.is_ok() {
+            self.viewers.push(stream);
+        }
+    }
+
+    fn send(&mut self, message: &str) {
+        self.viewers.retain(|mut viewer| viewer.write_all(message.as_bytes()).is_ok());
+    }
+}
+
+/// The parts of the stream spanning several lines.
+enum Block {
+    /// The piece set, with its source.
+    Pieces(String),
+    State,
+}
+
+/// A game watched from a server.
+pub struct Viewer {
+    lines: Receiver<String>,
+    /// The game, once its state has been received.
+    pub game: Option<Game>,
+    pub clear_progress: Option<f64>,
+    /// How the game ended, if it did.
+    pub over: Option<String>,
+    /// Piece set of the game, once received.
+    piece_set: Option<PieceSet>,
+    /// Block being received, and its lines so far.
+    block: Option<(Block, String)>,
+    events: Vec<Event>,
+}
+
+impl Viewer {
+    pub fn connect(address: &str) -> io::Result<Viewer> {
+        let stream = TcpStream::connect(address)?;
+        // Reading the stream blocks, so it's done in its own thread.
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stream).lines() {
+                match line {
+                    Ok(line) => if sender.send(line).is_err() { break },
+                    Err(_) => break,
+                }
+            }
+        });
+        Ok(Viewer {
+            lines: receiver,
+            game: None,
+            clear_progress: None,
+            over: None,
+            piece_set: None,
+            block: None,
+            events: Vec::new(),
+        })
+    }
+
+    /// Applies everything received since the last call. Returns `Ok(false)` once
+    /// the server closed the connection.
+    pub fn update(&mut self) -> Result<bool, String> {
+        loop {
+            match self.lines.try_recv() {
+                Ok(line) => self.apply(&line).map_err(|e| format!("invalid stream line \"{}\": {}", line, e))?,
+                Err(TryRecvError::Empty) => return Ok(true),
+                Err(TryRecvError::Disconnected) => return Ok(false),
+            }
+        }
+    }
+
+    /// Returns the events which happened since the last call.
+    pub fn take_events(&mut self) -> Vec<Event> {
+        ::std::mem::take(&mut self.events)
+    }
+
+    fn apply(&mut self, line: &str) -> save::Result<()> {
+        if let Some((_, ref mut content)) = self.block {
+            if line != "end" {
+                content.push_str(line);
+                content.push('\n');
+                return Ok(())
+            }
+        }
+        match self.block.take() {
+            Some((Block::Pieces(source), content)) => {
+                self.piece_set = Some(PieceSet::parse(&source, &content).map_err(save::Error::Corrupted)?);
+                return Ok(())
+            }
+            Some((Block::State, content)) => {
+                let piece_set = self.piece_set.clone().ok_or("no piece set received")?;
+                self.game = Some(save::from_save(&Fields::parse(&content)?, Some(piece_set))?);
+                return Ok(())
+            }
+            None => {}
+        }
+        let (command, args) = match line.find(' ') {
+            Some(pos) => (&line[..pos], &line[pos + 1..]),
+            None => (line, ""),
+        };
+        let values = args.split_whitespace().collect::<Vec<_>>();
+        match command {
+            "tetris-spectate" => {
+                let version = parse::<u32>(args, "protocol version")?;
+                if version != PROTOCOL_VERSION {
+                    return Err(save::Error::Corrupted(format!("unsupported protocol version {}", version)));
+                }
+            }
+            "pieces" => self.block = Some((Block::Pieces(args.to_string()), String::new())),
+            "state" => self.block = Some((Block::State, String::new())),
+            "over" => self.over = Some(args.to_string()),
+            _ => {
+                let game = self.game.as_mut().ok_or("no state received")?;
+                let tetris = &mut game.tetris;
+                match command {
+                    "time" if values.len() == 2 => {
+                        game.time = Duration::from_millis(parse(values[0], "time")?);
+                        self.clear_progress = values[1].parse().ok();
+                    }
+                    "cells" if values.len() % 3 == 0 => {
+                        for cell in values.chunks(3) {
+                            let (x, y) = (parse::<usize>(cell[0], "x")?, parse::<usize>(cell[1], "y")?);
+                            if x >= tetris.game_map.width() || y >= tetris.game_map.height() {
+                                return Err("cell out of the map".into());
+                            }
+                            tetris.game_map.set(x, y, parse(cell[2], "cell")?);
+                        }
+                    }
+                    "piece" if values == ["none"] => tetris.current_piece = None,
+                    "piece" if values.len() == 4 => {
+                        let mut piece = tetris.piece_set.spawn(parse(values[0], "piece")?).ok_or("invalid piece")?;
+                        piece.x = parse(values[1], "piece position")?;
+                        piece.y = parse(values[2], "piece position")?;
+                        piece.current_state = parse(values[3], "piece rotation")?;
+                        if piece.current_state as usize >= piece.states.len() ||
+                           !piece.test_loaded_position(&tetris.game_map) {
+                            return Err("the piece doesn't fit in the map".into());
+                        }
+                        tetris.current_piece = Some(piece);
+                    }
+                    "hold" if values.len() == 1 => {
+                        tetris.held_piece = match values[0] {
+                            "none" => None,
+                            id => {
+                                let id = parse::<u8>(id, "held piece")?;
+                                if id as usize >= tetris.piece_set.len() {
+                                    return Err("invalid held piece".into());
+                                }
+                                Some(id)
+                            }
+                        };
+                    }
+                    "score" if values.len() == 4 => {
+                        tetris.score = parse(values[0], "score")?;
+                        tetris.nb_lines = parse(values[1], "lines")?;
+                        tetris.current_level = parse(values[2], "level")?;
+                        tetris.nb_pieces = parse(values[3], "pieces")?;
+                    }
+                    "clear" if values.len() > 1 => {
+                        let rows = values[0].split(',').map(|y| parse(y, "line"))
+                                                       .collect::<save::Result<Vec<usize>>>()?;
+                        let board = values[1..].iter().map(|line| {
+                            line.chars().map(char_to_cell).collect::<Option<Vec<_>>>()
+                        }).collect::<Option<Vec<_>>>().ok_or("invalid cell in map")?;
+                        if board.len() != tetris.game_map.height() ||
+                           board.iter().any(|line| line.len() != tetris.game_map.width()) {
+                            return Err("invalid map size".into());
+                        }
+                        if rows.iter().any(|&y| y >= board.len()) {
+                            return Err("cleared line out of the map".into());
+                        }
+                        self.events.push(Event::Locked { rows, board });
+                    }
+                    "harddrop" => self.events.push(Event::HardDrop),
+                    _ => return Err("unknown message".into()),
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+// Statistics of a game, collected by the engine as it goes. The rates (pieces
+// per second, attack per minute...) only get computed at the end of the game,
+// from the time played.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+
+use mode::{as_millis, format_time, GameResult, Outcome};
+use save::{self, parse_list, Fields};
+
+/// Clears of this many lines or more are counted together.
+pub const MAX_CLEAR: usize = 5;
+/// Lines sent to an opponent, indexed by the number of lines cleared.
+const ATTACK: [u32; MAX_CLEAR + 1] = [0, 0, 1, 2, 4, 5];
+const TSPIN_ATTACK: [u32; 4] = [0, 2, 4, 6];
+/// Extra lines sent, indexed by the combo. Longer combos send the last value.
+const COMBO_ATTACK: [u32; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+const PERFECT_CLEAR_ATTACK: u32 = 10;
+const CLEAR_NAMES: [&str; MAX_CLEAR] = ["Singles", "Doubles", "Triples", "Tetrises", "Pentas+"];
+
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    /// Actions applied to the pieces by the player.
+    pub keys: u32,
+    /// Line clears, indexed by the number of lines minus one.
+    pub clears: [u32; MAX_CLEAR],
+    /// Lines which would have been sent to an opponent, with the usual rules.
+    pub attack: u32,
+    /// Pieces in a row which cleared lines.
+    combo: u32,
+    pub max_combo: u32,
+    // Whether the last clear was a tetris or a T-spin, which gives a bonus to
+    // the next one if it's one too.
+    back_to_back: bool,
+    pub back_to_backs: u32,
+    /// Pieces placed with more inputs than needed.
+    pub finesse_faults: u32,
+    /// Pieces received, indexed by piece id.
+    pub distribution: Vec<u32>,
+    /// Pieces received since the last one of each id.
+    droughts: Vec<u32>,
+    max_droughts: Vec<u32>,
+}
+
+impl Stats {
+    pub fn new(nb_pieces: usize) -> Stats {
+        Stats {
+            distribution: vec![0; nb_pieces],
+            droughts: vec![0; nb_pieces],
+            max_droughts: vec![0; nb_pieces],
+            ..Stats::default()
+        }
+    }
+
+    pub fn piece_spawned(&mut self, id: u8) {
+        for (other, drought) in self.droughts.iter_mut().enumerate() {
+            if other == id as usize {
+                self.max_droughts[other] = self.max_droughts[other].max(*drought);
+                *drought = 0;
+            } else {
+                *drought += 1;
+            }
+        }
+        self.distribution[id as usize] += 1;
+    }
+
+    pub fn piece_locked(&mut self, nb_lines: usize, tspin: bool, perfect_clear: bool) {
+        if nb_lines == 0 {
+            self.combo = 0;
+            return
+        }
+        self.clears[nb_lines.min(MAX_CLEAR) - 1] += 1;
+        self.combo += 1;
+        self.max_combo = self.max_combo.max(self.combo);
+
+        let difficult = tspin || nb_lines >= 4;
+        let mut attack = if tspin {
+            TSPIN_ATTACK[nb_lines.min(3)]
+        } else {
+            ATTACK[nb_lines.min(MAX_CLEAR)]
+        };
+        if difficult && self.back_to_back {
+            attack += 1;
+            self.back_to_backs += 1;
+        }
+        self.back_to_back = difficult;
+        attack += COMBO_ATTACK[(self.combo as usize - 1).min(COMBO_ATTACK.len() - 1)];
+        if perfect_clear {
+            attack += PERFECT_CLEAR_ATTACK;
+        }
+        self.attack += attack;
+    }
+
+    /// Longest wait for each piece, the current one included.
+    pub fn max_droughts(&self) -> Vec<u32> {
+        self.max_droughts.iter().zip(&self.droughts).map(|(&max, &current)| max.max(current)).collect()
+    }
+
+    pub fn to_save(&self) -> String {
+        let join = |values: &[u32]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" ");
+        format!("stats: {} {} {} {} {} {} {}\nclears: {}\ndistribution: {}\ndroughts: {}\nmax_droughts: {}\n",
+                self.keys, self.attack, self.combo, self.max_combo, self.back_to_back,
+                self.back_to_backs, self.finesse_faults, join(&self.clears),
+                join(&self.distribution), join(&self.droughts), join(&self.max_droughts))
+    }
+
+    pub fn from_save(fields: &Fields, nb_pieces: usize) -> save::Result<Stats> {
+        let values = fields.get("stats")?.split_whitespace().collect::<Vec<_>>();
+        if values.len() != 7 {
+            return Err("invalid stats".into());
+        }
+        let clears = parse_list(fields.get("clears")?, Some(MAX_CLEAR), "clears")?;
+        let mut stats = Stats {
+            keys: save::parse(values[0], "stats")?,
+            attack: save::parse(values[1], "stats")?,
+            combo: save::parse(values[2], "stats")?,
+            max_combo: save::parse(values[3], "stats")?,
+            back_to_back: save::parse(values[4], "stats")?,
+            back_to_backs: save::parse(values[5], "stats")?,
+            finesse_faults: save::parse(values[6], "stats")?,
+            distribution: parse_list(fields.get("distribution")?, Some(nb_pieces), "distribution")?,
+            droughts: parse_list(fields.get("droughts")?, Some(nb_pieces), "droughts")?,
+            max_droughts: parse_list(fields.get("max_droughts")?, Some(nb_pieces), "droughts")?,
+            ..Stats::default()
+        };
+        stats.clears.copy_from_slice(&clears);
+        Ok(stats)
+    }
+}
+
+/// The statistics of a finished game, with the rates computed.
+struct Report {
+    pieces_per_second: f64,
+    keys_per_piece: f64,
+    attack_per_minute: f64,
+    max_droughts: Vec<u32>,
+}
+
+impl Report {
+    fn new(result: &GameResult) -> Report {
+        let tetris = &result.tetris;
+        let stats = &tetris.stats;
+        let seconds = result.time.as_secs_f64().max(0.001);
+        Report {
+            pieces_per_second: tetris.nb_pieces as f64 / seconds,
+            keys_per_piece: if tetris.nb_pieces == 0 {
+                0.
+            } else {
+                stats.keys as f64 / tetris.nb_pieces as f64
+            },
+            attack_per_minute: stats.attack as f64 * 60. / seconds,
+            max_droughts: stats.max_droughts(),
+        }
+    }
+}
+
+/// Prints the statistics under the results of the game.
+pub fn print(result: &GameResult) {
+    let report = Report::new(result);
+    let tetris = &result.tetris;
+    let stats = &tetris.stats;
+    println!();
+    println!("Pieces/second:   {:.2}", report.pieces_per_second);
+    println!("Keys/piece:      {:.2}", report.keys_per_piece);
+    println!("Finesse faults:  {}", stats.finesse_faults);
+    println!("Attack/minute:   {:.1} ({} lines)", report.attack_per_minute, stats.attack);
+    for (name, nb) in CLEAR_NAMES.iter().zip(&stats.clears).filter(|&(_, &nb)| nb > 0) {
+        println!("{:<17}{}", format!("{}:", name), nb);
+    }
+    println!("Max combo:       {}", stats.max_combo);
+    println!("Back-to-backs:   {}", stats.back_to_backs);
+    println!("T-spins:         {} (mini/zero {}, single {}, double {}, triple {})",
+             tetris.nb_tspins.iter().sum::<u32>(), tetris.nb_tspins[0], tetris.nb_tspins[1],
+             tetris.nb_tspins[2], tetris.nb_tspins[3]);
+    println!("Perfect clears:  {}", tetris.nb_perfect_clears);
+    println!("Pieces (received/longest drought):");
+    for (id, piece) in tetris.piece_set.pieces.iter().enumerate() {
+        println!("    {:<6}{:>5} {:>5}", piece.name, stats.distribution[id], report.max_droughts[id]);
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.chars().flat_map(|c| match c {
+        '"' => vec!['\\', '"'],
+        '\\' => vec!['\\', '\\'],
+        c if (c as u32) < 0x20 => format!("\\u{:04x}", c as u32).chars().collect(),
+        c => vec![c],
+    }).collect()
+}
+
+fn json_list(values: &[u32]) -> String {
+    format!("[{}]", values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(","))
+}
+
+/// Formats the results and the statistics of the game as a single line JSON
+/// object.
+pub fn to_json(result: &GameResult) -> String {
+    let report = Report::new(result);
+    let tetris = &result.tetris;
+    let stats = &tetris.stats;
+    let outcome = match result.outcome {
+        Outcome::Won => "won",
+        Outcome::Lost(_) => "lost",
+        Outcome::Quit => "quit",
+    };
+    let game_over = match result.outcome {
+        Outcome::Lost(reason) => format!("\"{}\"", reason.description()),
+        _ => "null".to_string(),
+    };
+    let pieces = tetris.piece_set.pieces.iter().enumerate().map(|(id, piece)| {
+        format!("{{\"name\":\"{}\",\"count\":{},\"max_drought\":{}}}",
+                escape(&piece.name), stats.distribution[id], report.max_droughts[id])
+    }).collect::<Vec<_>>();
+    format!("{{\"mode\":\"{}\",\"piece_set\":\"{}\",\"outcome\":\"{}\",\"game_over\":{},\"time_ms\":{},\
+             \"time\":\"{}\",\"score\":{},\"lines\":{},\"level\":{},\"pieces\":{},\
+             \"keys\":{},\"pieces_per_second\":{:.3},\"keys_per_piece\":{:.3},\
+             \"finesse_faults\":{},\"attack\":{},\"attack_per_minute\":{:.3},\
+             \"clears\":{},\"max_combo\":{},\"back_to_backs\":{},\"tspins\":{},\"perfect_clears\":{},\"distribution\":[{}]}}",
+            tetris.mode.name(), escape(&tetris.piece_set.source), outcome, game_over, as_millis(result.time),
+            format_time(result.time), tetris.score, tetris.nb_lines, tetris.current_level,
+            tetris.nb_pieces, stats.keys, report.pieces_per_second, report.keys_per_piece,
+            stats.finesse_faults, stats.attack, report.attack_per_minute,
+            json_list(&stats.clears), stats.max_combo, stats.back_to_backs, json_list(&tetris.nb_tspins), tetris.nb_perfect_clears,
+            pieces.join(","))
+}
+
+/// Appends the statistics of the game to `file_name`, one JSON object per line.
+pub fn export(result: &GameResult, file_name: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(file_name)?;
+    writeln!(file, "{}", to_json(result))
+}
@@ -0,0 +1,84 @@
+use board::{row_bits, Board, Row};
+
+pub type Piece = Vec<Vec<u8>>;
+pub type States = Vec<Piece>;
+/// The bits of each line of a state, to test it against the rows of a `Board`.
+pub type Mask = Vec<Row>;
+
+/// Offsets (x, y) tried in order when rotating a piece, `y` going down.
+pub type Kicks = Vec<(isize, isize)>;
+
+/// A piece moving in the game map. Pieces are created by their `PieceSet`.
+#[derive(Clone, Debug)]
+pub struct Tetrimino {
+    /// Index of the piece in its set.
+    pub id: u8,
+    pub states: States,
+    pub masks: Vec<Mask>,
+    /// Kicks used when rotating from each state.
+    pub kicks: Vec<Kicks>,
+    pub x: isize,
+    pub y: usize,
+    pub current_state: u8,
+}
+
+impl Tetrimino {
+    /// Returns `false` if there was no room to rotate the piece.
+    pub fn rotate(&mut self, game_map: &Board) -> bool {
+        let mut tmp_state = self.current_state + 1;
+        if tmp_state as usize >= self.states.len() {
+            tmp_state = 0;
+        }
+        for &(x, y) in &self.kicks[self.current_state as usize] {
+            let new_y = self.y as isize + y;
+            if new_y >= 0 && self.test_position(game_map, tmp_state as usize,
+                                                self.x + x, new_y as usize) {
+                self.current_state = tmp_state;
+                self.x += x;
+                self.y = new_y as usize;
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn test_position(&self, game_map: &Board,
+                         tmp_state: usize, x: isize, y: usize) -> bool {
+        game_map.fits(&self.masks[tmp_state], x, y)
+    }
+
+    pub fn test_current_position(&self, game_map: &Board) -> bool {
+        self.test_position(game_map, self.current_state as usize, self.x, self.y)
+    }
+
+    /// `test_current_position` for a piece read from a save or a stream: a
+    /// position far out of the map is rejected before being tested, since
+    /// `Board::fits` would overflow on it.
+    pub fn test_loaded_position(&self, game_map: &Board) -> bool {
+        let size = self.width().max(self.states.iter().map(|state| state.len()).max().unwrap_or(0));
+        self.x >= -(size as isize) && self.x <= game_map.width() as isize &&
+        self.y <= game_map.height() && self.test_current_position(game_map)
+    }
+
+    pub fn change_position(&mut self, game_map: &Board, new_x: isize, new_y: usize) -> bool {
+        if self.test_position(game_map, self.current_state as usize, new_x, new_y) {
+            self.x = new_x;
+            self.y = new_y;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Width of the widest state.
+    pub fn width(&self) -> usize {
+        self.states.iter()
+                   .flat_map(|state| state.iter().map(|line| line.len()))
+                   .max()
+                   .unwrap_or(0)
+    }
+}
+
+pub fn mask(piece: &Piece) -> Mask {
+    piece.iter().map(|line| row_bits(line)).collect()
+}
@@ -0,0 +1,650 @@
+use std::mem;
+
+use rand;
+
+use board::{self, Board};
+use classic;
+use finesse::Finesse;
+use garbage::{GarbageGenerator, GARBAGE};
+use mode::{GameMode, GameOver};
+use observer::GameObserver;
+use pieces::PieceSet;
+use randomizer::Randomizer;
+use save::{self, parse, parse_list, Fields};
+use stats::Stats;
+use tetrimino::Tetrimino;
+
+pub const LEVEL_TIMES: [u32; 10] = [1000, 850, 700, 600, 500, 400, 300, 250, 221, 190];
+pub const LEVEL_LINES: [u32; 10] = [20,   40,  60,  80,  100, 120, 140, 160, 180, 200];
+/// Name of the piece which can do T-spins.
+const T_PIECE: &str = "T";
+
+/// Something that happened in the game, for the frontends to show.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// A piece got locked. `rows` are the lines it completed and `board` the map
+    /// before they were removed.
+    Locked { rows: Vec<usize>, board: Vec<Vec<u8>> },
+    HardDrop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    Rotate,
+    HardDrop,
+    /// Puts the current piece aside, taking out the one held before.
+    Hold,
+}
+
+pub struct Tetris {
+    pub game_map: Board,
+    pub current_level: u32,
+    pub score: u32,
+    pub nb_lines: u32,
+    pub nb_pieces: u32,
+    /// T-spins, indexed by the number of lines they cleared.
+    pub nb_tspins: [u32; 4],
+    pub nb_perfect_clears: u32,
+    pub current_piece: Option<Tetrimino>,
+    pub mode: GameMode,
+    pub piece_set: PieceSet,
+    pub stats: Stats,
+    pub finesse: Finesse,
+    /// Id of the piece put aside by the player.
+    pub held_piece: Option<u8>,
+    /// A piece can only be held once, until it's locked.
+    can_hold: bool,
+    /// Id of the piece taken out of the hold, spawning next. `None` means that
+    /// the next piece comes from the randomizer as usual.
+    next_from_hold: Option<u8>,
+    /// Set when the player tops out.
+    pub game_over: Option<GameOver>,
+    /// Time (in milliseconds) a piece takes to fall one line, replacing the one
+    /// of the level. Only mods change it, so it isn't saved.
+    pub gravity: Option<u32>,
+    /// Line of the lowest cell of the last locked piece, 0 being the bottom
+    /// line. The entry delay of the classic mode depends on it, and isn't saved
+    /// either.
+    pub lock_height: usize,
+    randomizer: Randomizer,
+    garbage: GarbageGenerator,
+    // Needed to detect T-spins.
+    last_move_rotation: bool,
+    events: Vec<Event>,
+    observers: Vec<Box<dyn GameObserver>>,
+}
+
+impl Tetris {
+    /// Starts a game with a random seed, or with the one of the date in the
+    /// daily challenge.
+    pub fn new(mode: GameMode, piece_set: PieceSet) -> Tetris {
+        let seed = match mode {
+            GameMode::Daily { date } => date.seed(),
+            _ => rand::random(),
+        };
+        Tetris::with_seed(seed, mode, piece_set)
+    }
+
+    /// The classic mode always plays with the pieces of the NES, and the daily
+    /// challenge with the standard ones.
+    pub fn with_seed(seed: u64, mode: GameMode, piece_set: PieceSet) -> Tetris {
+        let messy = match mode {
+            GameMode::Dig { messy, .. } => messy,
+            _ => false,
+        };
+        let (current_level, piece_set, randomizer) = match mode {
+            GameMode::Classic { start_level } => {
+                (start_level, PieceSet::classic(), Randomizer::nes(seed))
+            }
+            GameMode::Daily { .. } => (1, PieceSet::standard(), Randomizer::new(seed)),
+            _ => (1, piece_set, Randomizer::new(seed)),
+        };
+        let mut tetris = Tetris {
+            game_map: Board::empty(),
+            current_level,
+            score: 0,
+            nb_lines: 0,
+            nb_pieces: 0,
+            nb_tspins: [0; 4],
+            nb_perfect_clears: 0,
+            current_piece: None,
+            mode,
+            stats: Stats::new(piece_set.len()),
+            finesse: Finesse::default(),
+            held_piece: None,
+            can_hold: true,
+            next_from_hold: None,
+            game_over: None,
+            gravity: None,
+            lock_height: 0,
+            piece_set,
+            randomizer,
+            // The garbage doesn't use the same generator as the pieces so that
+            // the same seed gives the same pieces in every mode.
+            garbage: GarbageGenerator::new(!seed, messy),
+            last_move_rotation: false,
+            events: Vec::new(),
+            observers: Vec::new(),
+        };
+        if let GameMode::Dig { lines, .. } = mode {
+            tetris.add_garbage(lines);
+        }
+        tetris
+    }
+
+    fn update_score(&mut self, to_add: u32) {
+        self.score += to_add;
+    }
+
+    fn increase_level(&mut self) {
+        self.current_level += 1;
+        let level = self.current_level;
+        self.notify(|observer, _| observer.on_level_up(level));
+    }
+
+    fn increase_line(&mut self) {
+        self.nb_lines += 1;
+        if let GameMode::Classic { start_level } = self.mode {
+            if classic::level(start_level, self.nb_lines) > self.current_level {
+                self.increase_level();
+            }
+        } else if self.mode.has_level_progression() &&
+           (self.current_level as usize) < LEVEL_LINES.len() &&
+           self.nb_lines > LEVEL_LINES[self.current_level as usize - 1] {
+            self.increase_level();
+        }
+    }
+
+    /// Returns the lines which were removed, as they were numbered before.
+    fn check_lines(&mut self) -> Vec<usize> {
+        let rows = self.game_map.clear_full_lines();
+        let score_add = match self.mode {
+            GameMode::Classic { .. } => classic::line_score(rows.len(), self.current_level),
+            _ => {
+                let mut score_add = self.current_level * rows.len() as u32;
+                if rows.len() == self.game_map.height() {
+                    // A "tetris"!
+                    score_add += 1000;
+                }
+                score_add
+            }
+        };
+        self.update_score(score_add);
+        for _ in 0..rows.len() {
+            self.increase_line();
+        }
+        rows
+    }
+
+    /// Returns the events which happened since the last call.
+    pub fn take_events(&mut self) -> Vec<Event> {
+        mem::take(&mut self.events)
+    }
+
+    /// Observers are kept for the whole game, they aren't saved.
+    pub fn add_observer(&mut self, observer: Box<dyn GameObserver>) {
+        self.observers.push(observer);
+    }
+
+    fn notify<F: FnMut(&mut dyn GameObserver, &Tetris)>(&mut self, mut notify: F) {
+        if self.observers.is_empty() {
+            return
+        }
+        // They're taken out so that they can look at the game while notified.
+        let mut observers = mem::take(&mut self.observers);
+        for observer in &mut observers {
+            notify(observer.as_mut(), self);
+        }
+        self.observers = observers;
+    }
+
+    /// Notifies the observers of something about the current piece.
+    fn notify_piece<F: FnMut(&mut dyn GameObserver, &Tetrimino)>(&mut self, mut notify: F) {
+        self.notify(|observer, tetris| {
+            if let Some(ref piece) = tetris.current_piece {
+                notify(observer, piece);
+            }
+        });
+    }
+
+    /// Tells the observers that the game got lost. The engine doesn't know when
+    /// it is, since the goal of the mode may have been reached at the same time.
+    pub fn notify_game_over(&mut self, reason: GameOver) {
+        self.notify(|observer, _| observer.on_game_over(reason));
+    }
+
+    /// Returns `None` when the pieces of a fixed sequence ran out.
+    pub fn create_new_tetrimino(&mut self) -> Option<Tetrimino> {
+        let nb_pieces = self.piece_set.len();
+        self.randomizer.next_piece(nb_pieces).map(|nb| self.piece_set.spawn(nb).expect("invalid piece id"))
+    }
+
+    /// Replaces the random pieces with `pieces` (as given to `create_new_tetrimino`).
+    pub fn set_piece_sequence(&mut self, pieces: Vec<u8>) {
+        self.randomizer = Randomizer::with_sequence(pieces);
+    }
+
+    /// Id of the piece spawning after the current one, shown in the preview.
+    pub fn next_preview(&self) -> Option<u8> {
+        self.next_from_hold.or_else(|| self.randomizer.peek(self.piece_set.len()))
+    }
+
+    pub fn pieces_left(&self) -> Option<usize> {
+        self.randomizer.pieces_left()
+    }
+
+    /// A T-spin is a T locked right after a rotation with at least three of the
+    /// four cells diagonal to its centre occupied (walls count).
+    fn is_tspin(&self, piece: &Tetrimino) -> bool {
+        let state = &piece.states[piece.current_state as usize];
+        if !self.last_move_rotation || self.piece_set.find(T_PIECE) != Some(piece.id) {
+            return false;
+        }
+        let filled = |x: isize, y: isize| {
+            y >= 0 && (y as usize) < state.len() && x >= 0 && (x as usize) < state[y as usize].len() &&
+            state[y as usize][x as usize] != 0
+        };
+        let occupied = |x: isize, y: isize| self.game_map.is_occupied(x, y);
+        for y in 0..state.len() as isize {
+            for x in 0..state[y as usize].len() as isize {
+                // The centre is the only cell with three neighbours.
+                let neighbours = [(-1, 0), (1, 0), (0, -1), (0, 1)].iter()
+                                                                  .filter(|&&(dx, dy)| filled(x + dx, y + dy))
+                                                                  .count();
+                if filled(x, y) && neighbours == 3 {
+                    let (x, y) = (piece.x + x, piece.y as isize + y);
+                    return [(-1, -1), (1, -1), (-1, 1), (1, 1)].iter()
+                                                               .filter(|&&(dx, dy)| occupied(x + dx, y + dy))
+                                                               .count() >= 3;
+                }
+            }
+        }
+        false
+    }
+
+    pub fn make_permanent(&mut self) {
+        let tspin = match self.current_piece {
+            Some(ref piece) => self.is_tspin(piece),
+            None => false,
+        };
+        let locked = self.current_piece.is_some();
+        if let Some(ref piece) = self.current_piece {
+            let state = &piece.states[piece.current_state as usize];
+            let bottom = state.iter().rposition(|line| line.iter().any(|&cell| cell != 0)).unwrap_or(0);
+            if piece.y + bottom < board::VANISH_ZONE {
+                self.game_over = Some(GameOver::LockOut);
+            }
+            self.lock_height = self.game_map.height().saturating_sub(piece.y + bottom + 1);
+        }
+        if let Some(ref piece) = self.current_piece {
+            if self.finesse.piece_locked(piece) {
+                self.stats.finesse_faults += 1;
+            }
+        }
+        if locked {
+            self.notify_piece(|observer, piece| observer.on_lock(piece));
+        }
+        let mut to_add = 0;
+        if let Some(ref piece) = self.current_piece {
+            self.game_map.lock(&piece.states[piece.current_state as usize], piece.x, piece.y);
+            // The NES only scores lines.
+            match self.mode {
+                GameMode::Classic { .. } => {}
+                _ => to_add += self.current_level,
+            }
+            self.nb_pieces += 1;
+        }
+        self.update_score(to_add);
+        let board = self.game_map.lines().to_vec();
+        let rows = self.check_lines();
+        let nb_lines = rows.len();
+        if tspin {
+            self.nb_tspins[nb_lines.min(3)] += 1;
+        }
+        let perfect_clear = nb_lines > 0 && self.game_map.is_empty();
+        if perfect_clear {
+            self.nb_perfect_clears += 1;
+        }
+        if locked {
+            self.stats.piece_locked(nb_lines, tspin, perfect_clear);
+        }
+        if nb_lines > 0 {
+            self.notify(|observer, _| observer.on_lines_cleared(&rows, tspin, perfect_clear));
+        }
+        self.current_piece = None;
+        self.last_move_rotation = false;
+        self.can_hold = true;
+        self.events.push(Event::Locked { rows, board });
+    }
+
+    /// The piece coming out of the hold if there is one, the next one of the
+    /// randomizer otherwise.
+    fn next_piece(&mut self) -> Option<Tetrimino> {
+        match self.next_from_hold.take() {
+            Some(id) => self.piece_set.spawn(id),
+            None => {
+                let piece = self.create_new_tetrimino();
+                if let Some(ref piece) = piece {
+                    self.stats.piece_spawned(piece.id);
+                }
+                piece
+            }
+        }
+    }
+
+    /// Puts a new piece in the spawn lines, from which it drops one line right
+    /// away if it can. `initial` are the actions held while waiting for it
+    /// (IRS/IHS): with a hold, the piece goes straight into the hold, and with a
+    /// rotation, it spawns rotated if it fits that way. Returns `false` if the
+    /// pieces ran out or if the piece overlaps the map, which is a block out.
+    pub fn spawn_piece(&mut self, initial: &[Action]) -> bool {
+        let mut piece = match self.next_piece() {
+            Some(piece) => piece,
+            None => return false,
+        };
+        if initial.contains(&Action::Hold) && self.can_hold {
+            self.stats.keys += 1;
+            self.can_hold = false;
+            self.next_from_hold = self.held_piece.replace(piece.id);
+            piece = match self.next_piece() {
+                Some(piece) => piece,
+                None => return false,
+            };
+        }
+        if initial.contains(&Action::Rotate) {
+            let state = (piece.current_state + 1) % piece.states.len() as u8;
+            if piece.test_position(&self.game_map, state as usize, piece.x, piece.y) {
+                self.stats.keys += 1;
+                piece.current_state = state;
+            }
+        }
+        if !piece.test_current_position(&self.game_map) {
+            self.game_over = Some(GameOver::BlockOut);
+            return false;
+        }
+        if piece.test_position(&self.game_map, piece.current_state as usize, piece.x, piece.y + 1) {
+            piece.y += 1;
+        }
+        self.finesse.piece_spawned(&piece, &self.game_map);
+        self.current_piece = Some(piece);
+        self.notify_piece(|observer, piece| observer.on_spawn(piece));
+        true
+    }
+
+    /// Puts the current piece aside. The piece held before, or the next one if
+    /// there was none, spawns in its place.
+    fn hold(&mut self) {
+        if !self.can_hold {
+            return
+        }
+        if let Some(piece) = self.current_piece.take() {
+            self.can_hold = false;
+            self.last_move_rotation = false;
+            self.next_from_hold = self.held_piece.replace(piece.id);
+        }
+    }
+
+    /// Moves the current piece one line down. Returns `true` if it couldn't move
+    /// and got locked instead.
+    pub fn fall(&mut self) -> bool {
+        let mut make_permanent = false;
+        if let Some(ref mut piece) = self.current_piece {
+            let x = piece.x;
+            let y = piece.y + 1;
+            make_permanent = !piece.change_position(&self.game_map, x, y);
+            if !make_permanent {
+                self.last_move_rotation = false;
+            }
+        }
+        if make_permanent {
+            self.make_permanent();
+        } else {
+            self.notify_piece(|observer, piece| observer.on_move(piece));
+        }
+        make_permanent
+    }
+
+    pub fn hard_drop(&mut self) {
+        let mut moved = false;
+        if let Some(ref mut piece) = self.current_piece {
+            self.events.push(Event::HardDrop);
+            let x = piece.x;
+            let mut y = piece.y;
+            while piece.change_position(&self.game_map, x, y + 1) {
+                y += 1;
+                moved = true;
+                self.last_move_rotation = false;
+            }
+        }
+        if moved {
+            self.notify_piece(|observer, piece| observer.on_move(piece));
+        }
+        self.make_permanent();
+    }
+
+    /// Returns `true` if the action locked the current piece or put it aside.
+    pub fn apply_action(&mut self, action: Action) -> bool {
+        if self.current_piece.is_none() || !self.mode.allows(action) {
+            return false;
+        }
+        self.stats.keys += 1;
+        match action {
+            Action::SoftDrop => {
+                self.finesse.action(action, false);
+                return self.fall();
+            }
+            Action::HardDrop => {
+                self.hard_drop();
+                return true;
+            }
+            Action::Hold => {
+                self.hold();
+                return self.current_piece.is_none();
+            }
+            _ => {}
+        }
+        let mut moved = false;
+        if let Some(ref mut piece) = self.current_piece {
+            let (x, y) = (piece.x, piece.y);
+            moved = match action {
+                Action::MoveLeft => piece.change_position(&self.game_map, x - 1, y),
+                Action::MoveRight => piece.change_position(&self.game_map, x + 1, y),
+                Action::Rotate => piece.rotate(&self.game_map),
+                Action::SoftDrop | Action::HardDrop | Action::Hold => unreachable!(),
+            };
+            if moved {
+                self.last_move_rotation = action == Action::Rotate;
+            }
+            let dx = match action {
+                Action::MoveLeft => -1,
+                Action::MoveRight => 1,
+                _ => 0,
+            };
+            let blocked = dx != 0 && !piece.test_position(&self.game_map, piece.current_state as usize,
+                                                          piece.x + dx, piece.y);
+            self.finesse.action(action, blocked);
+        }
+        match action {
+            Action::Rotate if moved => self.notify_piece(|observer, piece| observer.on_rotate(piece)),
+            _ if moved => self.notify_piece(|observer, piece| observer.on_move(piece)),
+            _ => {}
+        }
+        false
+    }
+
+    /// Pushes `nb` lines of garbage from the bottom of the map. Returns `false`
+    /// if blocks got pushed out of the top of the map, which is a top out.
+    pub fn add_garbage(&mut self, nb: u32) -> bool {
+        let width = self.game_map.width();
+        let mut topped_out = false;
+        for _ in 0..nb {
+            let line = self.garbage.next_line(width);
+            topped_out |= !self.game_map.push_line(line);
+        }
+        // The current piece goes up with the rest of the map if it has to.
+        if let Some(ref mut piece) = self.current_piece {
+            while !piece.test_current_position(&self.game_map) && piece.y > 0 {
+                piece.y -= 1;
+            }
+            topped_out |= !piece.test_current_position(&self.game_map);
+        }
+        if topped_out {
+            self.game_over = Some(GameOver::GarbageTopOut);
+        }
+        !topped_out
+    }
+
+    pub fn garbage_left(&self) -> usize {
+        self.game_map.lines().iter().filter(|line| line.contains(&GARBAGE)).count()
+    }
+
+    /// The cell at (`x`, `y`) as it's shown, with the current piece over the map,
+    /// `y` counting from the top of the visible lines. Cells out of the map are
+    /// empty.
+    pub fn visible_cell(&self, x: usize, y: usize) -> u8 {
+        let y = y + board::VANISH_ZONE;
+        if let Some(ref piece) = self.current_piece {
+            let shift_x = x as isize - piece.x;
+            if y >= piece.y && shift_x >= 0 {
+                let state = &piece.states[piece.current_state as usize];
+                match state.get(y - piece.y).and_then(|line| line.get(shift_x as usize)) {
+                    Some(&cell) if cell != 0 => return cell,
+                    _ => {}
+                }
+            }
+        }
+        self.game_map.lines().get(y).and_then(|line| line.get(x)).cloned().unwrap_or(0)
+    }
+
+    /// Time (in milliseconds) the current piece takes to fall one line.
+    pub fn fall_delay(&self) -> u32 {
+        self.gravity.unwrap_or_else(|| match self.mode {
+            GameMode::Classic { .. } => classic::fall_delay(self.current_level),
+            _ => LEVEL_TIMES[self.current_level as usize - 1],
+        })
+    }
+
+    /// Formats the whole game state as the "key: value" lines of a save file.
+    pub fn to_save(&self) -> String {
+        let map = self.game_map.lines().iter()
+                               .map(|line| line.iter().map(|&cell| save::cell_to_char(cell)).collect::<String>())
+                               .collect::<Vec<_>>();
+        let piece = match self.current_piece {
+            Some(ref piece) => format!("{} {} {} {}", piece.id, piece.x, piece.y, piece.current_state),
+            None => "none".to_string(),
+        };
+        let piece_id = |id: Option<u8>| id.map(|id| id.to_string()).unwrap_or_else(|| "none".to_string());
+        format!("mode: {}\npiece_set: {}\nlevel: {}\nscore: {}\nlines: {}\npieces: {}\n\
+                 tspins: {} {} {} {}\nperfect_clears: {}\nlast_move_rotation: {}\nmap: {}\n\
+                 piece: {}\nhold: {} {} {}\nrandomizer: {}\ngarbage: {}\n{}",
+                self.mode.to_save(), self.piece_set.source, self.current_level, self.score,
+                self.nb_lines, self.nb_pieces,
+                self.nb_tspins[0], self.nb_tspins[1], self.nb_tspins[2], self.nb_tspins[3],
+                self.nb_perfect_clears, self.last_move_rotation, map.join(" "), piece,
+                piece_id(self.held_piece), self.can_hold, piece_id(self.next_from_hold),
+                self.randomizer.to_save(), self.garbage.to_save(), self.stats.to_save())
+    }
+
+    /// `piece_set` replaces the one named in the save, whose file may not be
+    /// there.
+    pub fn from_save(fields: &Fields, piece_set: Option<PieceSet>) -> save::Result<Tetris> {
+        let piece_set = match piece_set {
+            Some(piece_set) => piece_set,
+            None => PieceSet::load(fields.get("piece_set")?).map_err(save::Error::Corrupted)?,
+        };
+        let mut game_map = Vec::new();
+        for line in fields.get("map")?.split_whitespace() {
+            let cells = line.chars().map(save::char_to_cell)
+                                    .collect::<Option<Vec<_>>>()
+                                    .filter(|cells| {
+                                        cells.iter().all(|&c| c == GARBAGE || c as usize <= piece_set.len())
+                                    })
+                                    .ok_or("invalid cell in map")?;
+            if cells.len() != board::WIDTH {
+                return Err(save::Error::Corrupted(format!("map lines have to be {} cells wide",
+                                                       board::WIDTH)));
+            }
+            game_map.push(cells);
+        }
+        if game_map.len() != board::HEIGHT {
+            return Err(save::Error::Corrupted(format!("the map has to be {} lines high", board::HEIGHT)));
+        }
+        let game_map = Board::from_lines(game_map);
+        let mode = GameMode::from_save(fields.get("mode")?)?;
+        let current_level = fields.parse_value("level")?;
+        // Classic levels go on past the gravity table.
+        let valid_level = match mode {
+            GameMode::Classic { start_level } => current_level >= start_level,
+            _ => current_level >= 1 && current_level as usize <= LEVEL_TIMES.len(),
+        };
+        if !valid_level {
+            return Err("invalid level".into());
+        }
+        let tspins = parse_list(fields.get("tspins")?, Some(4), "T-spins")?;
+        let current_piece = match fields.get("piece")? {
+            "none" => None,
+            piece => {
+                let values = piece.split_whitespace().collect::<Vec<_>>();
+                if values.len() != 4 {
+                    return Err("invalid piece".into());
+                }
+                let mut tetrimino = piece_set.spawn(parse(values[0], "piece")?).ok_or("invalid piece")?;
+                tetrimino.x = parse(values[1], "piece position")?;
+                tetrimino.y = parse(values[2], "piece position")?;
+                tetrimino.current_state = parse(values[3], "piece rotation")?;
+                if tetrimino.current_state as usize >= tetrimino.states.len() ||
+                   !tetrimino.test_loaded_position(&game_map) {
+                    return Err("the piece doesn't fit in the map".into());
+                }
+                Some(tetrimino)
+            }
+        };
+        // The held piece, whether the current piece can be held, and the piece
+        // which came out of the hold if it hasn't spawned yet.
+        let hold = fields.get("hold")?.split_whitespace().collect::<Vec<_>>();
+        if hold.len() != 3 {
+            return Err("invalid hold".into());
+        }
+        let hold_id = |value: &str| -> save::Result<Option<u8>> {
+            match value {
+                "none" => Ok(None),
+                value => {
+                    let id = parse::<u8>(value, "held piece")?;
+                    if id as usize >= piece_set.len() {
+                        return Err("invalid held piece".into());
+                    }
+                    Ok(Some(id))
+                }
+            }
+        };
+        let (held_piece, next_from_hold) = (hold_id(hold[0])?, hold_id(hold[2])?);
+        Ok(Tetris {
+            game_map,
+            current_level,
+            score: fields.parse_value("score")?,
+            nb_lines: fields.parse_value("lines")?,
+            nb_pieces: fields.parse_value("pieces")?,
+            nb_tspins: [tspins[0], tspins[1], tspins[2], tspins[3]],
+            nb_perfect_clears: fields.parse_value("perfect_clears")?,
+            current_piece,
+            mode,
+            randomizer: Randomizer::from_save(fields.get("randomizer")?, piece_set.len())?,
+            stats: Stats::from_save(fields, piece_set.len())?,
+            finesse: Finesse::default(),
+            held_piece,
+            can_hold: parse(hold[1], "hold")?,
+            next_from_hold,
+            game_over: None,
+            gravity: None,
+            lock_height: 0,
+            garbage: GarbageGenerator::from_save(fields.get("garbage")?)?,
+            piece_set,
+            last_move_rotation: fields.parse_value("last_move_rotation")?,
+            events: Vec::new(),
+            observers: Vec::new(),
+        })
+    }
+}
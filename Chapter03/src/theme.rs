@@ -0,0 +1,203 @@
+// Themes change the look of the SDL version of the game. A theme is a directory
+// of "themes" holding:
+//
+//     palette.txt      colours, made of "key = R G B" lines
+//     atlas.png        a row of square tiles, the Nth one being used for the
+//                      piece of id N
+//     background.png   drawn behind everything
+//     grid.png         drawn behind the map
+//     border.png       drawn around the map
+//
+// Every file is optional: missing images are replaced by solid textures of the
+// palette's colours, and missing tiles by the colours of the piece set. The
+// palette keys are "background", "text", "grid", "border", "garbage" and
+// "piece N" to replace the colour of the piece of id N.
+//
+// The accessibility settings go over the theme: a colour scheme other than the
+// standard one replaces the colours and the tiles of the pieces, and the
+// high-contrast mode replaces every image and colour but the pieces' ones.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tetris::accessibility::{self, ColorScheme};
+use tetris::garbage::{GARBAGE, GARBAGE_COLOR};
+use tetris::read_from_file;
+use tetris::settings::Settings;
+use tetris::PieceSet;
+
+pub const THEMES_DIR: &str = "themes";
+/// Name of the theme built in the game, only made of solid colours.
+pub const BUILTIN: &str = "default";
+const PALETTE_FILE: &str = "palette.txt";
+pub const ATLAS_FILE: &str = "atlas.png";
+
+pub type Rgb = (u8, u8, u8);
+
+#[derive(Clone, Debug)]
+pub struct Palette {
+    pub background: Rgb,
+    pub text: Rgb,
+    pub grid: Rgb,
+    pub border: Rgb,
+    pub garbage: Rgb,
+    /// Replacement colours, indexed by piece id.
+    pub pieces: Vec<Option<Rgb>>,
+    /// Goes over the colours of the pieces.
+    pub scheme: ColorScheme,
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette {
+            background: (255, 0, 0),
+            text: (255, 255, 255),
+            grid: (0, 0, 0),
+            border: (255, 255, 255),
+            garbage: GARBAGE_COLOR,
+            pieces: Vec::new(),
+            scheme: ColorScheme::Standard,
+        }
+    }
+}
+
+impl Palette {
+    fn parse(content: &str) -> Result<Palette, String> {
+        let mut palette = Palette::default();
+        for (nb, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let error = |message: String| Err(format!("{}: {}", nb + 1, message));
+            let pos = match line.find('=') {
+                Some(pos) => pos,
+                None => return error(format!("expected \"key = R G B\", got \"{}\"", line)),
+            };
+            let (key, value) = (line[..pos].trim(), line[pos + 1..].trim());
+            let color = match parse_color(value) {
+                Some(color) => color,
+                None => return error(format!("invalid color \"{}\"", value)),
+            };
+            match key {
+                "background" => palette.background = color,
+                "text" => palette.text = color,
+                "grid" => palette.grid = color,
+                "border" => palette.border = color,
+                "garbage" => palette.garbage = color,
+                _ if key.starts_with("piece ") => {
+                    let id = match key["piece ".len()..].trim().parse::<usize>() {
+                        Ok(id) => id,
+                        Err(_) => return error(format!("invalid piece id in \"{}\"", key)),
+                    };
+                    if palette.pieces.len() <= id {
+                        palette.pieces.resize(id + 1, None);
+                    }
+                    palette.pieces[id] = Some(color);
+                }
+                _ => return error(format!("unknown key \"{}\"", key)),
+            }
+        }
+        Ok(palette)
+    }
+
+    /// Colour of a cell of the game map.
+    pub fn color(&self, piece_set: &PieceSet, cell: u8) -> Rgb {
+        match cell {
+            GARBAGE => self.garbage,
+            _ => self.scheme.piece_color(cell - 1)
+                            .or_else(|| self.pieces.get(cell as usize - 1).cloned().unwrap_or(None))
+                            .unwrap_or_else(|| piece_set.color(cell)),
+        }
+    }
+}
+
+fn parse_color(value: &str) -> Option<Rgb> {
+    let parts = value.split_whitespace().map(|v| v.parse::<u8>().ok()).collect::<Option<Vec<_>>>()?;
+    match parts.len() {
+        3 => Some((parts[0], parts[1], parts[2])),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Theme {
+    pub name: String,
+    /// `None` for the built-in theme.
+    dir: Option<PathBuf>,
+    pub palette: Palette,
+    pub high_contrast: bool,
+}
+
+impl Theme {
+    pub fn builtin() -> Theme {
+        Theme {
+            name: BUILTIN.to_string(),
+            dir: None,
+            palette: Palette::default(),
+            high_contrast: false,
+        }
+    }
+
+    /// Loads the theme `name` from the themes directory, `BUILTIN` giving the
+    /// built-in one.
+    pub fn load(name: &str) -> Result<Theme, String> {
+        if name == BUILTIN {
+            return Ok(Theme::builtin());
+        }
+        let dir = Path::new(THEMES_DIR).join(name);
+        if !dir.is_dir() {
+            return Err(format!("no theme \"{}\" in \"{}\"", name, THEMES_DIR));
+        }
+        let palette_file = dir.join(PALETTE_FILE);
+        let palette = match read_from_file(&palette_file.to_string_lossy()) {
+            Ok(content) => {
+                Palette::parse(&content).map_err(|e| format!("{}:{}", palette_file.display(), e))?
+            }
+            Err(_) => Palette::default(),
+        };
+        Ok(Theme {
+            name: name.to_string(),
+            dir: Some(dir),
+            palette,
+            high_contrast: false,
+        })
+    }
+
+    /// Applies the accessibility settings.
+    pub fn accessible(mut self, settings: &Settings) -> Theme {
+        self.palette.scheme = settings.color_scheme;
+        self.high_contrast = settings.high_contrast;
+        if self.high_contrast {
+            self.palette.background = accessibility::HIGH_CONTRAST_BACKGROUND;
+            self.palette.grid = accessibility::HIGH_CONTRAST_BACKGROUND;
+            self.palette.text = accessibility::HIGH_CONTRAST_FOREGROUND;
+            self.palette.border = accessibility::HIGH_CONTRAST_FOREGROUND;
+            self.palette.garbage = accessibility::HIGH_CONTRAST_GARBAGE;
+        }
+        self
+    }
+
+    /// Path of an image of the theme, if the theme has it and the accessibility
+    /// settings don't replace it.
+    pub fn image(&self, file_name: &str) -> Option<PathBuf> {
+        if self.high_contrast || (file_name == ATLAS_FILE && self.palette.scheme != ColorScheme::Standard) {
+            return None;
+        }
+        self.dir.as_ref().map(|dir| dir.join(file_name)).filter(|path| path.is_file())
+    }
+}
+
+/// Names of the available themes, the built-in one first.
+pub fn list() -> Vec<String> {
+    let mut names = fs::read_dir(THEMES_DIR).map(|entries| {
+        entries.filter_map(|entry| entry.ok())
+               .filter(|entry| entry.path().is_dir())
+               .map(|entry| entry.file_name().to_string_lossy().into_owned())
+               .filter(|name| name != BUILTIN)
+               .collect::<Vec<_>>()
+    }).unwrap_or_default();
+    names.sort();
+    names.insert(0, BUILTIN.to_string());
+    names
+}
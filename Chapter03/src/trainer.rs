@@ -0,0 +1,218 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::thread;
+
+use ai::{self, Weights, FEATURE_NAMES, NB_FEATURES};
+use mode::GameMode;
+use pieces::PieceSet;
+use randomizer::XorShift;
+use tetris::Tetris;
+
+const USAGE: &str = "\
+Usage: tetris train [OPTIONS]
+
+Options:
+    --population N    number of weight sets per generation (default: 50)
+    --generations N   number of generations to run (default: 30)
+    --games N         games played by every weight set per generation (default: 5)
+    --max-pieces N    pieces after which a game is stopped (default: 500)
+    --threads N       number of games run in parallel (default: 4)
+    --seed N          seed of the whole training run (default: random)
+    --log FILE        CSV file receiving per-generation statistics (default: training.csv)
+    --output FILE     file receiving the best weights (default: weights.txt)";
+
+// Part of the population kept as is from one generation to the next.
+const ELITE_RATIO: f64 = 0.2;
+const TOURNAMENT_RATIO: f64 = 0.1;
+const MUTATION_RATE: f64 = 0.05;
+const MUTATION_STEP: f64 = 0.2;
+
+struct Options {
+    population: usize,
+    generations: u32,
+    games: usize,
+    max_pieces: u32,
+    threads: usize,
+    seed: u64,
+    log_file: String,
+    output_file: String,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options {
+            population: 50,
+            generations: 30,
+            games: 5,
+            max_pieces: 500,
+            threads: 4,
+            seed: ::rand::random(),
+            log_file: "training.csv".to_string(),
+            output_file: "weights.txt".to_string(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let value = args.next().ok_or_else(|| format!("missing value for `{}`", arg))?;
+            let number = || value.parse::<u64>()
+                                 .map_err(|_| format!("invalid value for `{}`: {}", arg, value));
+            match arg.as_str() {
+                "--population" => options.population = number()? as usize,
+                "--generations" => options.generations = number()? as u32,
+                "--games" => options.games = number()? as usize,
+                "--max-pieces" => options.max_pieces = number()? as u32,
+                "--threads" => options.threads = number()? as usize,
+                "--seed" => options.seed = number()?,
+                "--log" => options.log_file = value.clone(),
+                "--output" => options.output_file = value.clone(),
+                _ => return Err(format!("unknown option `{}`", arg)),
+            }
+        }
+        if options.population < 2 || options.games == 0 || options.threads == 0 {
+            return Err("population must be at least 2, games and threads at least 1".to_string());
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Clone)]
+struct Individual {
+    weights: Weights,
+    fitness: f64,
+}
+
+pub fn run(args: &[String]) {
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}\n\n{}", e, USAGE);
+            return
+        }
+    };
+    if let Err(e) = train(&options) {
+        println!("Training failed: {}", e);
+    }
+}
+
+fn train(options: &Options) -> io::Result<()> {
+    let mut rng = XorShift::new(options.seed);
+    let mut log = File::create(&options.log_file)?;
+    writeln!(log, "generation,best,average,worst,{}", FEATURE_NAMES.join(","))?;
+
+    let mut population = (0..options.population).map(|_| {
+        let mut weights = Weights::new([0.; NB_FEATURES]);
+        for value in weights.values.iter_mut() {
+            *value = rng.next_f64() * 2. - 1.;
+        }
+        weights.normalize();
+        Individual { weights, fitness: 0. }
+    }).collect::<Vec<_>>();
+
+    println!("Training with seed {}", options.seed);
+    for generation in 0..options.generations {
+        // Every weight set plays the same games so that their fitness can be compared.
+        let seeds = (0..options.games).map(|_| rng.next_u64()).collect::<Vec<_>>();
+        evaluate_population(&mut population, &seeds, options);
+        population.sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap());
+
+        let best = &population[0];
+        let worst = &population[population.len() - 1];
+        let average = population.iter().map(|i| i.fitness).sum::<f64>() / population.len() as f64;
+        writeln!(log, "{},{},{},{},{}", generation, best.fitness, average, worst.fitness,
+                 best.weights.values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(","))?;
+        log.flush()?;
+        // We save at every generation so that an interrupted training isn't lost.
+        best.weights.save(&options.output_file)?;
+        println!("Generation {}: best {:.1} lines, average {:.1} lines",
+                 generation, best.fitness, average);
+
+        population = next_generation(&population, &mut rng);
+    }
+    println!("Best weights written to \"{}\"", options.output_file);
+    Ok(())
+}
+
+fn evaluate_population(population: &mut [Individual], seeds: &[u64], options: &Options) {
+    let chunk_size = population.len().div_ceil(options.threads);
+    let handles = population.chunks(chunk_size).map(|chunk| {
+        let weights = chunk.iter().map(|i| i.weights.clone()).collect::<Vec<_>>();
+        let seeds = seeds.to_vec();
+        let max_pieces = options.max_pieces;
+        thread::spawn(move || {
+            weights.iter().map(|weights| {
+                let lines = seeds.iter()
+                                 .map(|&seed| play_game(weights, seed, max_pieces))
+                                 .sum::<u32>();
+                lines as f64 / seeds.len() as f64
+            }).collect::<Vec<_>>()
+        })
+    }).collect::<Vec<_>>();
+
+    let mut fitnesses = Vec::with_capacity(population.len());
+    for handle in handles {
+        fitnesses.extend(handle.join().expect("training thread panicked"));
+    }
+    for (individual, fitness) in population.iter_mut().zip(fitnesses) {
+        individual.fitness = fitness;
+    }
+}
+
+/// Plays a whole game with the bot and returns the number of cleared lines.
+pub fn play_game(weights: &Weights, seed: u64, max_pieces: u32) -> u32 {
+    let mut tetris = Tetris::with_seed(seed, GameMode::Endless, PieceSet::standard());
+    for _ in 0..max_pieces {
+        if !tetris.spawn_piece(&[]) || !ai::move_piece(&mut tetris, weights) {
+            break
+        }
+        tetris.hard_drop();
+    }
+    tetris.nb_lines
+}
+
+// `population` has to be sorted from the best to the worst.
+fn next_generation(population: &[Individual], rng: &mut XorShift) -> Vec<Individual> {
+    let nb_elites = ((population.len() as f64 * ELITE_RATIO) as usize).max(1);
+    let tournament_size = ((population.len() as f64 * TOURNAMENT_RATIO) as usize).max(2);
+    let mut next = population[..nb_elites].to_vec();
+
+    while next.len() < population.len() {
+        let first = tournament(population, tournament_size, rng);
+        let second = tournament(population, tournament_size, rng);
+        let mut weights = crossover(first, second);
+        mutate(&mut weights, rng);
+        weights.normalize();
+        next.push(Individual { weights, fitness: 0. });
+    }
+    next
+}
+
+fn tournament<'a>(population: &'a [Individual], size: usize,
+                  rng: &mut XorShift) -> &'a Individual {
+    let mut best = &population[rng.gen_range(0, population.len() as u32) as usize];
+    for _ in 1..size {
+        let candidate = &population[rng.gen_range(0, population.len() as u32) as usize];
+        if candidate.fitness > best.fitness {
+            best = candidate;
+        }
+    }
+    best
+}
+
+// The child is closer to the parent that played better.
+fn crossover(first: &Individual, second: &Individual) -> Weights {
+    let total = first.fitness + second.fitness;
+    let ratio = if total > 0. { first.fitness / total } else { 0.5 };
+    let mut weights = Weights::new([0.; NB_FEATURES]);
+    for i in 0..NB_FEATURES {
+        weights.values[i] = first.weights.values[i] * ratio +
+                            second.weights.values[i] * (1. - ratio);
+    }
+    weights
+}
+
+fn mutate(weights: &mut Weights, rng: &mut XorShift) {
+    for value in weights.values.iter_mut() {
+        if rng.next_f64() < MUTATION_RATE {
+            *value += (rng.next_f64() * 2. - 1.) * MUTATION_STEP;
+        }
+    }
+}
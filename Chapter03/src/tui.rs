@@ -0,0 +1,187 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use tetris::accessibility::{cell_letter, text_color, HIGH_CONTRAST_FOREGROUND};
+use tetris::animation::CellEffect;
+use tetris::board::VANISH_ZONE;
+use tetris::{Action, GameResult};
+use tetris::settings::Settings;
+use spectator::Viewer;
+use {play, watch as watch_game, Frame, Frontend, GameOptions};
+
+// We don't want an extra dependency just to switch the terminal to raw mode, so
+// we let `stty` do it.
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("`stty {}` failed", args.join(" "))));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+struct Terminal {
+    saved_mode: String,
+    input: Receiver<Vec<u8>>,
+    settings: Settings,
+}
+
+impl Terminal {
+    fn new(settings: &Settings) -> io::Result<Terminal> {
+        let saved_mode = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+
+        // Reading stdin blocks, so it's done in its own thread.
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0; 16];
+            let stdin = io::stdin();
+            let mut stdin = stdin.lock();
+            while let Ok(size) = stdin.read(&mut buffer) {
+                if size == 0 || sender.send(buffer[..size].to_vec()).is_err() {
+                    break
+                }
+            }
+        });
+        // We clear the screen and hide the cursor.
+        print!("\x1b[2J\x1b[?25l");
+        Ok(Terminal {
+            saved_mode,
+            input: receiver,
+            settings: settings.clone(),
+        })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h\r\n");
+        let _ = io::stdout().flush();
+        let _ = stty(&[&self.saved_mode]);
+    }
+}
+
+impl Frontend for Terminal {
+    fn poll_actions(&mut self) -> Option<Vec<Action>> {
+        let mut actions = Vec::new();
+        loop {
+            let bytes = match self.input.try_recv() {
+                Ok(bytes) => bytes,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return None,
+            };
+            let mut i = 0;
+            while i < bytes.len() {
+                match bytes[i] {
+                    // Arrow keys are sent as "ESC [ A" to "ESC [ D".
+                    0x1b if bytes.get(i + 1) == Some(&b'[') => {
+                        match bytes.get(i + 2) {
+                            Some(&b'A') => actions.push(Action::Rotate),
+                            Some(&b'B') => actions.push(Action::SoftDrop),
+                            Some(&b'C') => actions.push(Action::MoveRight),
+                            Some(&b'D') => actions.push(Action::MoveLeft),
+                            _ => {}
+                        }
+                        i += 2;
+                    }
+                    // Escape, 'q' and Ctrl+C.
+                    0x1b | b'q' | 3 => return None,
+                    b' ' => actions.push(Action::HardDrop),
+                    b'c' => actions.push(Action::Hold),
+                    _ => {}
+                }
+                i += 1;
+            }
+        }
+        Some(actions)
+    }
+
+    fn draw(&mut self, frame: &Frame) {
+        let tetris = frame.tetris;
+        let animations = frame.animations;
+        let settings = &self.settings;
+        // The high-contrast mode draws the frame in bright white over black,
+        // with nothing in the empty cells.
+        let (frame_style, empty) = if settings.high_contrast {
+            let (r, g, b) = HIGH_CONTRAST_FOREGROUND;
+            (format!("\x1b[1;40;38;2;{};{};{}m", r, g, b), "\x1b[40m  \x1b[0m")
+        } else {
+            (String::new(), " .")
+        };
+        let mut board = animations.board().unwrap_or(tetris.game_map.lines()).to_vec();
+        if let Some(ref piece) = tetris.current_piece {
+            for (shift_y, line) in piece.states[piece.current_state as usize].iter().enumerate() {
+                for (shift_x, &cell) in line.iter().enumerate() {
+                    if cell != 0 {
+                        board[piece.y + shift_y][(piece.x + shift_x as isize) as usize] = cell;
+                    }
+                }
+            }
+        }
+        let width = board[0].len();
+        let mut particles = vec![vec![None; width]; board.len()];
+        for particle in animations.particles() {
+            if particle.x >= 0. && particle.y >= 0. &&
+               (particle.x as usize) < width && (particle.y as usize) < board.len() {
+                particles[particle.y as usize][particle.x as usize] = Some(particle);
+            }
+        }
+        // Cells are too big to shake the map smoothly, it's moved by a whole column.
+        let indent = if animations.shake().0.abs() > 0.05 { " " } else { "" };
+        let mut side = frame.hud.iter().map(|&(label, ref value)| format!("{}: {}", label, value))
+                          .collect::<Vec<_>>();
+        side.extend_from_slice(&[String::new(),
+                                 "Arrows: move/rotate".to_string(),
+                                 "Space:  hard drop".to_string(),
+                                 "c:      hold".to_string(),
+                                 "q/Esc:  quit".to_string()]);
+
+        // The terminal is in raw mode so every line has to end with "\r\n".
+        let mut screen = format!("\x1b[H{}{}┌{}┐\x1b[0m\x1b[K\r\n", indent, frame_style, "──".repeat(width));
+        for (y, line) in board.iter().enumerate().skip(VANISH_ZONE) {
+            screen.push_str(indent);
+            screen.push_str(&format!("{}│\x1b[0m", frame_style));
+            for (x, &cell) in line.iter().enumerate() {
+                let effect = animations.cell_effect(x, y);
+                if let Some(particle) = particles[y][x] {
+                    let (r, g, b) = settings.color_scheme.color(&tetris.piece_set, particle.color);
+                    let c = if particle.fade() > 0.5 { '*' } else { '·' };
+                    screen.push_str(&format!("\x1b[38;2;{};{};{}m {}\x1b[0m", r, g, b, c));
+                } else if cell == 0 || effect == CellEffect::Hidden {
+                    screen.push_str(empty);
+                } else if let CellEffect::Flash(_) = effect {
+                    screen.push_str("\x1b[97m██\x1b[0m");
+                } else if settings.patterns {
+                    // The letter is written over the colour of the piece.
+                    let color = settings.color_scheme.color(&tetris.piece_set, cell);
+                    let (r, g, b) = text_color(color);
+                    screen.push_str(&format!("\x1b[48;2;{};{};{}m\x1b[38;2;{};{};{}m{} \x1b[0m",
+                                             color.0, color.1, color.2, r, g, b,
+                                             cell_letter(&tetris.piece_set, cell)));
+                } else {
+                    let (r, g, b) = settings.color_scheme.color(&tetris.piece_set, cell);
+                    screen.push_str(&format!("\x1b[38;2;{};{};{}m██\x1b[0m", r, g, b));
+                }
+            }
+            screen.push_str(&format!("{}│\x1b[0m  {}{}\x1b[0m\x1b[K\r\n", frame_style, frame_style,
+                                     side.get(y - VANISH_ZONE).map(|s| s.as_str()).unwrap_or("")));
+        }
+        screen.push_str(&format!("{}{}└{}┘\x1b[0m\x1b[K\r\n", indent, frame_style, "──".repeat(width)));
+
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        let _ = stdout.write_all(screen.as_bytes());
+        let _ = stdout.flush();
+    }
+}
+
+pub fn run(options: GameOptions) -> Result<GameResult, String> {
+    let mut terminal = Terminal::new(&options.settings).map_err(|e| e.to_string())?;
+    Ok(play(&mut terminal, options))
+}
+
+pub fn watch(viewer: Viewer, settings: &Settings) -> Result<(), String> {
+    let mut terminal = Terminal::new(settings).map_err(|e| e.to_string())?;
+    watch_game(&mut terminal, viewer, settings)
+}
@@ -0,0 +1,188 @@
+// Browser version of the game: the engine of the native versions compiled to
+// WebAssembly, with a `WebGame` which the page drives. "index.html" forwards the
+// keys to it, advances it at every animation frame and has it draw itself on a
+// canvas. Build it, and run its tests in Node, with wasm-pack:
+//
+//     wasm-pack build --target web
+//     wasm-pack test --node
+//
+// The page then has to be served over HTTP from this directory, for example with
+// `python3 -m http.server`.
+
+extern crate tetris;
+extern crate wasm_bindgen;
+extern crate web_sys;
+
+use std::time::Duration;
+
+use tetris::garbage::{GARBAGE, GARBAGE_COLOR};
+use tetris::{Action, Game, GameMode, Outcome, PieceSet, Tetris, TICK};
+use wasm_bindgen::prelude::*;
+use web_sys::CanvasRenderingContext2d;
+
+/// Ticks simulated at most in a single frame, as in the native versions.
+const MAX_CATCH_UP_TICKS: u32 = 5;
+/// Size of a cell on the canvas, in pixels.
+const CELL: f64 = 32.;
+const GRID_X: f64 = 40.;
+const GRID_Y: f64 = 100.;
+pub const CANVAS_WIDTH: u32 = 400;
+pub const CANVAS_HEIGHT: u32 = 640;
+const BACKGROUND: &str = "rgb(255, 0, 0)";
+const GRID: &str = "rgb(0, 0, 0)";
+const TEXT: &str = "rgb(255, 255, 255)";
+
+/// The action of a key, as named by `KeyboardEvent.key`.
+pub fn action_for_key(key: &str) -> Option<Action> {
+    match key {
+        "ArrowLeft" => Some(Action::MoveLeft),
+        "ArrowRight" => Some(Action::MoveRight),
+        "ArrowDown" => Some(Action::SoftDrop),
+        "ArrowUp" => Some(Action::Rotate),
+        " " => Some(Action::HardDrop),
+        "c" | "C" | "Shift" => Some(Action::Hold),
+        _ => None,
+    }
+}
+
+fn css_color((r, g, b): (u8, u8, u8)) -> String {
+    format!("rgb({}, {}, {})", r, g, b)
+}
+
+#[wasm_bindgen]
+pub struct WebGame {
+    game: Game,
+    /// Actions of the keys pressed since the last tick.
+    pending: Vec<Action>,
+    /// Time the simulation is late on the page.
+    lag: Duration,
+    outcome: Option<Outcome>,
+}
+
+#[wasm_bindgen]
+impl WebGame {
+    /// Starts a game of `mode` (as given to `--mode`) with the standard pieces.
+    /// The same seed gives the same pieces as in the native versions.
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u32, mode: &str) -> Result<WebGame, JsValue> {
+        let mode = GameMode::from_name(mode)
+            .ok_or_else(|| JsValue::from_str(&format!("unknown mode \"{}\"", mode)))?;
+        Ok(WebGame {
+            game: Game::new(Tetris::with_seed(seed as u64, mode, PieceSet::standard())),
+            pending: Vec::new(),
+            lag: Duration::new(0, 0),
+            outcome: None,
+        })
+    }
+
+    /// Returns `false` if the key isn't used by the game, so that the page can
+    /// leave it to the browser.
+    pub fn key_down(&mut self, key: &str) -> bool {
+        match action_for_key(key) {
+            Some(action) => {
+                self.pending.push(action);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Advances the game by the time since the last frame.
+    pub fn update(&mut self, elapsed_ms: f64) {
+        if self.outcome.is_some() {
+            return
+        }
+        // Background tabs get very few frames: the missed time is dropped.
+        let seconds = if elapsed_ms > 0. { (elapsed_ms / 1000.).min(1.) } else { 0. };
+        let elapsed = Duration::from_secs_f64(seconds);
+        self.lag = (self.lag + elapsed).min(TICK * MAX_CATCH_UP_TICKS);
+        while self.lag >= TICK && self.outcome.is_none() {
+            self.lag -= TICK;
+            self.outcome = self.game.tick(&self.pending, None);
+            self.pending.clear();
+        }
+        // There are no animations to give them to.
+        self.game.take_events();
+    }
+
+    /// "running", "won" or "lost".
+    pub fn status(&self) -> String {
+        match self.outcome {
+            None => "running",
+            Some(Outcome::Won) => "won",
+            Some(Outcome::Lost(_)) | Some(Outcome::Quit) => "lost",
+        }.to_string()
+    }
+
+    /// Why the game got lost ("block out", "lock out" or "garbage top out"), or
+    /// an empty string.
+    pub fn game_over(&self) -> String {
+        match self.outcome {
+            Some(Outcome::Lost(reason)) => reason.description().to_string(),
+            _ => String::new(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.game.tetris.game_map.width() as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        self.game.tetris.game_map.visible_height() as u32
+    }
+
+    /// The cell at (`x`, `y`) of the visible lines with the falling piece: 0 if it's empty, the id
+    /// of its piece plus one or 255 for garbage.
+    pub fn cell(&self, x: u32, y: u32) -> u8 {
+        self.game.tetris.visible_cell(x as usize, y as usize)
+    }
+
+    pub fn score(&self) -> u32 {
+        self.game.tetris.score
+    }
+
+    pub fn lines(&self) -> u32 {
+        self.game.tetris.nb_lines
+    }
+
+    /// Draws the game on a `CANVAS_WIDTH` x `CANVAS_HEIGHT` canvas, the same way
+    /// as the SDL version without a theme.
+    pub fn draw(&self, context: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+        let tetris = &self.game.tetris;
+        let (width, height) = (self.width(), self.height());
+        context.set_fill_style_str(BACKGROUND);
+        context.fill_rect(0., 0., CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64);
+        context.set_fill_style_str(GRID);
+        context.fill_rect(GRID_X, GRID_Y, width as f64 * CELL, height as f64 * CELL);
+        for y in 0..height {
+            for x in 0..width {
+                let cell = self.cell(x, y);
+                if cell == 0 {
+                    continue
+                }
+                let color = if cell == GARBAGE {
+                    GARBAGE_COLOR
+                } else {
+                    tetris.piece_set.pieces[cell as usize - 1].color
+                };
+                context.set_fill_style_str(&css_color(color));
+                context.fill_rect(GRID_X + x as f64 * CELL, GRID_Y + y as f64 * CELL, CELL, CELL);
+            }
+        }
+        // The HUD goes above the grid, one element per line.
+        context.set_fill_style_str(TEXT);
+        context.set_font("16px monospace");
+        for (i, &(label, ref value)) in self.game.hud().iter().enumerate() {
+            context.fill_text(&format!("{}: {}", label, value), GRID_X, 24. + i as f64 * 20.)?;
+        }
+        match self.outcome {
+            Some(Outcome::Won) => context.fill_text("You won!", GRID_X, GRID_Y - 12.)?,
+            Some(Outcome::Lost(reason)) => {
+                context.fill_text(&format!("Game over ({})", reason.description()), GRID_X, GRID_Y - 12.)?
+            }
+            Some(Outcome::Quit) => context.fill_text("Game over", GRID_X, GRID_Y - 12.)?,
+            None => {}
+        }
+        Ok(())
+    }
+}
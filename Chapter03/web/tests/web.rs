@@ -0,0 +1,77 @@
+// Run in Node with `wasm-pack test --node`.
+
+extern crate tetris_web;
+extern crate wasm_bindgen_test;
+
+use tetris_web::WebGame;
+use wasm_bindgen_test::*;
+
+/// A frame at 60 frames per second, in milliseconds.
+const FRAME: f64 = 1000. / 60.;
+
+/// Columns of the falling piece.
+fn piece_columns(game: &WebGame) -> Vec<u32> {
+    (0..game.width()).filter(|&x| (0..4).any(|y| game.cell(x, y) != 0)).collect()
+}
+
+#[wasm_bindgen_test]
+fn unknown_modes_are_refused() {
+    assert!(WebGame::new(1, "tetris99").is_err());
+}
+
+#[wasm_bindgen_test]
+fn only_game_keys_are_handled() {
+    let mut game = WebGame::new(1, "endless").unwrap();
+    assert!(game.key_down("ArrowLeft"));
+    assert!(game.key_down(" "));
+    assert!(!game.key_down("F5"));
+}
+
+#[wasm_bindgen_test]
+fn arrow_keys_move_the_piece() {
+    let mut game = WebGame::new(7, "endless").unwrap();
+    game.update(FRAME);
+    let columns = piece_columns(&game);
+    assert!(!columns.is_empty());
+    game.key_down("ArrowLeft");
+    game.update(FRAME);
+    let moved = piece_columns(&game);
+    assert_eq!(moved, columns.iter().map(|&x| x - 1).collect::<Vec<_>>());
+}
+
+#[wasm_bindgen_test]
+fn dropping_every_piece_in_place_loses() {
+    let mut game = WebGame::new(3, "endless").unwrap();
+    for _ in 0..10_000 {
+        if game.status() != "running" {
+            break
+        }
+        game.key_down(" ");
+        game.update(FRAME);
+    }
+    assert_eq!(game.status(), "lost");
+    // The stack grows into the vanish zone until a new piece overlaps it.
+    assert_eq!(game.game_over(), "block out");
+    assert_eq!(game.lines(), 0);
+    assert!(game.score() > 0);
+}
+
+#[wasm_bindgen_test]
+fn same_seed_same_game() {
+    let mut games = vec![WebGame::new(11, "sprint").unwrap(), WebGame::new(11, "sprint").unwrap()];
+    for i in 0..600 {
+        for game in &mut games {
+            if i % 30 == 0 {
+                game.key_down("ArrowRight");
+                game.key_down(" ");
+            }
+            game.update(FRAME);
+        }
+    }
+    let cells = |game: &WebGame| {
+        (0..game.height()).flat_map(|y| (0..game.width()).map(move |x| (x, y)))
+                          .map(|(x, y)| game.cell(x, y))
+                          .collect::<Vec<_>>()
+    };
+    assert_eq!(cells(&games[0]), cells(&games[1]));
+}